@@ -3,7 +3,7 @@ use crate::{
     ray::{Point3, Ray},
 };
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct AABB {
     x: Interval,
     y: Interval,
@@ -12,7 +12,9 @@ pub struct AABB {
 
 impl AABB {
     pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
-        Self { x, y, z }
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
     }
 
     pub fn with_points(a: &Point3, b: &Point3) -> Self {
@@ -31,7 +33,9 @@ impl AABB {
         } else {
             Interval::new(b[2], a[2])
         };
-        Self { x, y, z }
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
     }
     pub fn with_boxes(box1: &AABB, box2: &AABB) -> Self {
         Self {
@@ -60,6 +64,21 @@ impl AABB {
         )
     }
 
+    fn pad_to_minimums(&mut self) {
+        // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+        // Planar primitives such as quads would otherwise produce a zero-width box.
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta)
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta)
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta)
+        }
+    }
+
     pub fn longest_axis(&self) -> i32 {
         if self.x.size() > self.y.size() {
             if self.x.size() > self.z.size() {
@@ -77,6 +96,11 @@ impl AABB {
     }
 
     pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.hit_interval(r, ray_t).is_some()
+    }
+
+    /// Returns the part of ray_t during which the ray is inside the box, if any
+    pub fn hit_interval(&self, r: &Ray, ray_t: Interval) -> Option<Interval> {
         let ray_orig = r.origin();
         let ray_dir = r.direction();
         let mut ray_t = ray_t;
@@ -101,9 +125,9 @@ impl AABB {
                 }
             }
             if ray_t.max <= ray_t.min {
-                return false;
+                return None;
             }
         }
-        return true;
+        Some(ray_t)
     }
 }
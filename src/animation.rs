@@ -0,0 +1,490 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image::{ImageError, ImageFormat, Rgb, RgbImage};
+
+use crate::{
+    aabb::AABB,
+    camera::{Camera, CameraBuilder, CameraError},
+    hittable::{HitRecord, Hittable, HittableList},
+    interval::Interval,
+    ray::{Point3, Ray},
+    transform::{hit_all_transformed, hit_transformed, Affine},
+    vec3::Vec3,
+};
+
+/// Placement of an object at a point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub position: Vec3,
+    pub rotation: Vec3, // degrees around x, then y, then z
+}
+
+impl Keyframe {
+    pub fn new(time: f64, position: Vec3, rotation: Vec3) -> Self {
+        Self {
+            time,
+            position,
+            rotation,
+        }
+    }
+
+    /// Rotates, then moves to `position`
+    pub fn transform(&self) -> Affine {
+        Affine::rotation(Vec3::new(1., 0., 0.), self.rotation.x())
+            .then(&Affine::rotation(Vec3::new(0., 1., 0.), self.rotation.y()))
+            .then(&Affine::rotation(Vec3::new(0., 0., 1.), self.rotation.z()))
+            .then(&Affine::translation(self.position))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Catmull-Rom spline through the keyframes, smooth at every keyframe
+    Spline,
+}
+
+/// Keyframes sorted by time. Before the first and after the last keyframe the placement holds
+/// still.
+#[derive(Debug, Clone)]
+pub struct Track {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl Track {
+    /// Panics if there are no keyframes
+    pub fn new(mut keyframes: Vec<Keyframe>, interpolation: Interpolation) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "Track requires at least one keyframe"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            keyframes,
+            interpolation,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn at(&self, time: f64) -> Keyframe {
+        let keys = &self.keyframes;
+        // Index of the first keyframe after `time`
+        let next = keys.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return Keyframe { time, ..keys[0] };
+        }
+        if next == keys.len() {
+            return Keyframe {
+                time,
+                ..keys[keys.len() - 1]
+            };
+        }
+        let (k1, k2) = (&keys[next - 1], &keys[next]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        let (position, rotation) = match self.interpolation {
+            Interpolation::Linear => (
+                k1.position + (k2.position - k1.position) * t,
+                k1.rotation + (k2.rotation - k1.rotation) * t,
+            ),
+            Interpolation::Spline => {
+                // The end keyframes are repeated to get tangents at the ends of the track
+                let k0 = &keys[next.saturating_sub(2)];
+                let k3 = &keys[usize::min(next + 1, keys.len() - 1)];
+                (
+                    catmull_rom(k0.position, k1.position, k2.position, k3.position, t),
+                    catmull_rom(k0.rotation, k1.rotation, k2.rotation, k3.rotation, t),
+                )
+            }
+        };
+        Keyframe::new(time, position, rotation)
+    }
+
+    pub fn transform_at(&self, time: f64) -> Affine {
+        self.at(time).transform()
+    }
+}
+
+// Point at t in [0,1] of the segment from p1 to p2 of a uniform Catmull-Rom spline
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f64) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.
+        + (p2 - p0) * t
+        + (p0 * 2. - p1 * 5. + p2 * 4. - p3) * t2
+        + (p3 - p0 + (p1 - p2) * 3.) * t3)
+        * 0.5
+}
+
+// Number of placements the bounding box is computed from
+const BBOX_STEPS: usize = 64;
+
+/// Moves any Hittable along a Track. Each ray sees the object where the track places it at the
+/// time of the ray, which gives motion blur over the shutter interval of the camera.
+#[derive(Debug)]
+pub struct Animated {
+    object: Arc<dyn Hittable>,
+    track: Track,
+    bbox: AABB,
+}
+
+impl Animated {
+    /// `shutter_open` and `shutter_close` are the times rays can have, the bounding box covers
+    /// the object everywhere in between
+    pub fn new(
+        object: Arc<dyn Hittable>,
+        track: Track,
+        shutter_open: f64,
+        shutter_close: f64,
+    ) -> Self {
+        let bbox = swept_box(&object.bounding_box(), &track, shutter_open, shutter_close);
+        Self {
+            object,
+            track,
+            bbox,
+        }
+    }
+}
+
+// Box around `bbox` carried along the track between two times. Placements are sampled at
+// regular steps and at every keyframe in between, and the box grows by the farthest any corner
+// moves in one step, to cover the corners between the samples.
+fn swept_box(bbox: &AABB, track: &Track, start: f64, end: f64) -> AABB {
+    let (x, y, z) = (
+        bbox.axis_interval(0),
+        bbox.axis_interval(1),
+        bbox.axis_interval(2),
+    );
+    if x.size() < 0. || y.size() < 0. || z.size() < 0. {
+        return AABB::empty();
+    }
+    let corners: Vec<Point3> = (0..8)
+        .map(|i| {
+            Point3::new(
+                if i & 1 == 0 { x.min } else { x.max },
+                if i & 2 == 0 { y.min } else { y.max },
+                if i & 4 == 0 { z.min } else { z.max },
+            )
+        })
+        .collect();
+
+    let mut times: Vec<f64> = (0..=BBOX_STEPS)
+        .map(|i| start + (end - start) * i as f64 / BBOX_STEPS as f64)
+        .chain(
+            track
+                .keyframes()
+                .iter()
+                .map(|k| k.time)
+                .filter(|t| (start..=end).contains(t)),
+        )
+        .collect();
+    times.sort_by(f64::total_cmp);
+
+    let mut swept = AABB::empty();
+    let mut step = 0.;
+    let mut previous: Option<Vec<Point3>> = None;
+    for time in times {
+        let transform = track.transform_at(time);
+        let placed: Vec<Point3> = corners
+            .iter()
+            .map(|c| transform.transform_point(c))
+            .collect();
+        if let Some(previous) = &previous {
+            for (a, b) in previous.iter().zip(&placed) {
+                step = f64::max(step, (*b - *a).length());
+            }
+        }
+        swept = AABB::with_boxes(&swept, &transform.transform_box(bbox));
+        previous = Some(placed);
+    }
+    AABB::new(
+        swept.axis_interval(0).expand(step),
+        swept.axis_interval(1).expand(step),
+        swept.axis_interval(2).expand(step),
+    )
+}
+
+impl Hittable for Animated {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let forward = self.track.transform_at(r.time());
+        // Rotations and translations are always invertible
+        let inverse = forward.inverse().unwrap();
+        hit_transformed(self.object.as_ref(), &forward, &inverse, r, ray_t, rec)
+    }
+
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        let forward = self.track.transform_at(r.time());
+        let inverse = forward.inverse().unwrap();
+        hit_all_transformed(self.object.as_ref(), &forward, &inverse, r, ray_t)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+/// Where the camera is and what it looks at, at one point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub lookfrom: Point3,
+    pub lookat: Point3,
+    pub vfov: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraPose {
+    pub fn of(camera: &Camera) -> Self {
+        Self {
+            lookfrom: camera.lookfrom,
+            lookat: camera.lookat,
+            vfov: camera.vfov,
+            focus_dist: camera.focus_dist,
+        }
+    }
+}
+
+type SceneFn = dyn Fn(f64, f64) -> (Arc<dyn Hittable>, HittableList);
+
+/// A sequence of frames, with time in seconds starting at 0 for the first frame. The scene is
+/// built again for every frame, given the times its shutter opens and closes, so objects can be
+/// placed with a Transform for that time or an Animated covering the interval.
+pub struct Animation {
+    pub frame_count: u32,
+    pub frame_rate: f64, // frames per second
+    pub shutter: f64,    // fraction of each frame the shutter is open, 0.5 is a 180 degree shutter
+    camera: Box<dyn Fn(f64) -> CameraPose>,
+    scene: Box<SceneFn>,
+}
+
+impl Animation {
+    pub fn new(
+        frame_count: u32,
+        frame_rate: f64,
+        camera: impl Fn(f64) -> CameraPose + 'static,
+        scene: impl Fn(f64, f64) -> (Arc<dyn Hittable>, HittableList) + 'static,
+    ) -> Self {
+        Self {
+            frame_count,
+            frame_rate,
+            shutter: 0.5,
+            camera: Box::new(camera),
+            scene: Box::new(scene),
+        }
+    }
+
+    /// Time at which the shutter opens for `frame`, counting from 1
+    pub fn frame_time(&self, frame: u32) -> f64 {
+        frame.saturating_sub(1) as f64 / self.frame_rate
+    }
+
+    pub fn frame_path(directory: &Path, frame: u32) -> PathBuf {
+        directory.join(format!("frame_{:04}.png", frame))
+    }
+
+    /// First frame without an image in `directory`, past the last frame if there are none left
+    pub fn first_missing_frame(&self, directory: &Path) -> u32 {
+        (1..=self.frame_count)
+            .find(|&frame| !Self::frame_path(directory, frame).exists())
+            .unwrap_or(self.frame_count + 1)
+    }
+
+    /// Renders frames `first_frame..=frame_count` with the settings of `camera` into
+    /// `directory`, calling `on_frame` after each image is written. Frames that already have an
+    /// image are skipped, so running it again resumes an interrupted sequence; delete the images
+    /// to render them again. Images are written under another name first and then renamed, so
+    /// an interrupted render never leaves a partial frame behind.
+    pub fn render(
+        &self,
+        camera: &Camera,
+        directory: &Path,
+        first_frame: u32,
+        mut on_frame: impl FnMut(u32),
+    ) -> Result<(), AnimationError> {
+        let frame_rate_valid = self.frame_rate > 0. && self.frame_rate.is_finite();
+        if !frame_rate_valid || !(0. ..=1.).contains(&self.shutter) {
+            return Err(AnimationError::InvalidTiming {
+                frame_rate: self.frame_rate,
+                shutter: self.shutter,
+            });
+        }
+        fs::create_dir_all(directory).map_err(ImageError::IoError)?;
+        for frame in u32::max(first_frame, 1)..=self.frame_count {
+            let path = Self::frame_path(directory, frame);
+            if path.exists() {
+                continue;
+            }
+            let open = self.frame_time(frame);
+            let close = open + self.shutter / self.frame_rate;
+            let pose = (self.camera)(open);
+            let frame_camera = CameraBuilder::from_camera(camera)
+                .lookfrom(pose.lookfrom)
+                .lookat(pose.lookat)
+                .vfov(pose.vfov)
+                .focus_dist(pose.focus_dist)
+                .shutter(open, close)
+                .build()
+                .map_err(|error| AnimationError::Camera { frame, error })?;
+            let (world, lights) = (self.scene)(open, close);
+            let pixels = frame_camera.render(&world, &lights);
+
+            let width = frame_camera.image_width as u32;
+            let image = RgbImage::from_fn(width, frame_camera.image_height as u32, |x, y| {
+                Rgb(pixels[(y * width + x) as usize].get_rgb())
+            });
+            let partial = path.with_extension("png.partial");
+            image.save_with_format(&partial, ImageFormat::Png)?;
+            fs::rename(&partial, &path).map_err(ImageError::IoError)?;
+            on_frame(frame);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum AnimationError {
+    InvalidTiming { frame_rate: f64, shutter: f64 },
+    Camera { frame: u32, error: CameraError },
+    Image(ImageError),
+}
+
+impl From<ImageError> for AnimationError {
+    fn from(error: ImageError) -> Self {
+        AnimationError::Image(error)
+    }
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationError::InvalidTiming {
+                frame_rate,
+                shutter,
+            } => write!(
+                f,
+                "invalid frame rate {} or shutter {}, which must be in [0,1]",
+                frame_rate, shutter
+            ),
+            AnimationError::Camera { frame, error } => write!(f, "frame {}: {}", frame, error),
+            AnimationError::Image(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian, sphere::Sphere};
+
+    #[test]
+    fn tracks_interpolate_between_keyframes() {
+        let keyframes = vec![
+            Keyframe::new(1., Vec3::new(2., 0., 0.), Vec3::new(0., 90., 0.)),
+            Keyframe::new(0., Vec3::new(0., 0., 0.), Vec3::new(0., 0., 0.)),
+            Keyframe::new(2., Vec3::new(2., 2., 0.), Vec3::new(0., 90., 0.)),
+        ];
+        let linear = Track::new(keyframes.clone(), Interpolation::Linear);
+        assert_eq!(linear.at(0.5).position, Vec3::new(1., 0., 0.));
+        assert_eq!(linear.at(0.5).rotation, Vec3::new(0., 45., 0.));
+        assert_eq!(linear.at(3.).position, Vec3::new(2., 2., 0.));
+        assert_eq!(linear.at(-1.).position, Vec3::new(0., 0., 0.));
+
+        // The spline passes through the keyframes too, but bends between them
+        let spline = Track::new(keyframes, Interpolation::Spline);
+        assert!((spline.at(1.).position - Vec3::new(2., 0., 0.)).length() < 1e-12);
+        assert!(spline.at(1.5).position.x() > 2.);
+    }
+
+    #[test]
+    fn sequences_resume_with_the_missing_frames() {
+        let directory = std::env::temp_dir().join(format!("rrtm_frames_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let camera = Camera::builder()
+            .image_width(4)
+            .image_height(2)
+            .samples_per_pixel(1)
+            .build()
+            .unwrap();
+        let mut animation = Animation::new(
+            3,
+            24.,
+            |time| CameraPose {
+                lookfrom: Point3::new(time, 0., 0.),
+                lookat: Point3::new(time, 0., -1.),
+                vfov: 90.,
+                focus_dist: 1.,
+            },
+            |_, _| (Arc::new(HittableList::new()), HittableList::new()),
+        );
+        assert_eq!(animation.first_missing_frame(&directory), 1);
+
+        let mut rendered = Vec::new();
+        animation
+            .render(&camera, &directory, 1, |frame| rendered.push(frame))
+            .unwrap();
+        assert_eq!(rendered, vec![1, 2, 3]);
+        assert!(directory.join("frame_0003.png").exists());
+        assert_eq!(animation.first_missing_frame(&directory), 4);
+
+        // Only the missing frame is rendered again
+        fs::remove_file(directory.join("frame_0002.png")).unwrap();
+        assert_eq!(animation.first_missing_frame(&directory), 2);
+        rendered.clear();
+        animation
+            .render(&camera, &directory, 1, |frame| rendered.push(frame))
+            .unwrap();
+        assert_eq!(rendered, vec![2]);
+
+        fs::remove_file(directory.join("frame_0001.png")).unwrap();
+        animation.shutter = 1.5;
+        assert!(matches!(
+            animation.render(&camera, &directory, 1, |_| {}),
+            Err(AnimationError::InvalidTiming { .. })
+        ));
+        assert!(!directory.join("frame_0001.png").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn bounding_box_covers_the_motion() {
+        // A unit sphere swinging around the y axis at a distance of 3
+        let sphere = Arc::new(Sphere::new(
+            Point3::new(3., 0., 0.),
+            1.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let track = Track::new(
+            vec![
+                Keyframe::new(0., Vec3::default(), Vec3::default()),
+                Keyframe::new(1., Vec3::default(), Vec3::new(0., 180., 0.)),
+            ],
+            Interpolation::Linear,
+        );
+        let animated = Animated::new(sphere, track.clone(), 0., 1.);
+        let bbox = animated.bounding_box();
+        for i in 0..=100 {
+            let center = track
+                .transform_at(i as f64 / 100.)
+                .transform_point(&Point3::new(3., 0., 0.));
+            for c in 0..3 {
+                let axis = bbox.axis_interval(c as i32);
+                assert!(axis.min <= center[c] - 1. && center[c] + 1. <= axis.max);
+            }
+        }
+
+        // Rays at different times see the sphere in different places
+        let r = |time| Ray::new_tm(Point3::new(3., 0., 5.), Vec3::new(0., 0., -1.), time);
+        let mut rec = HitRecord::default();
+        assert!(animated.hit(&r(0.), Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(!animated.hit(&r(1.), Interval::new(0.001, f64::INFINITY), &mut rec));
+    }
+}
@@ -0,0 +1,129 @@
+use image::{ImageBuffer, ImageResult, Luma, Rgb, RgbImage};
+
+use crate::{color::Color, interval::Interval, utils::sample_seed, vec3::Vec3};
+
+/// Arbitrary output variables: what the first surface seen through each pixel looks like,
+/// for compositing and denoising. Values are averaged over the samples of a pixel, except for
+/// the object ID which comes from its first sample. Pixels that only see the background are
+/// zero everywhere.
+#[derive(Debug, Clone, Default)]
+pub struct Aovs {
+    pub width: usize,
+    pub height: usize,
+    pub depth: Vec<f64>,   // distance from the camera along its viewing direction
+    pub normal: Vec<Vec3>, // world space normal facing the camera
+    pub albedo: Vec<Color>,
+    pub uv: Vec<(f64, f64)>,
+    pub object_id: Vec<u32>, // see hittable::ObjectId
+}
+
+/// The AOVs of a single camera sample
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AovSample {
+    pub depth: f64,
+    pub normal: Vec3,
+    pub albedo: Color,
+    pub uv: (f64, f64),
+    pub object_id: u32,
+}
+
+impl AovSample {
+    /// Average of the samples of a pixel, with the object ID of the first one since IDs cannot
+    /// be blended
+    pub fn average(samples: &[AovSample]) -> AovSample {
+        let Some(first) = samples.first() else {
+            return AovSample::default();
+        };
+        let scale = 1. / samples.len() as f64;
+        let mut pixel = AovSample {
+            object_id: first.object_id,
+            ..Default::default()
+        };
+        for sample in samples {
+            pixel.depth += sample.depth * scale;
+            pixel.normal += sample.normal * scale;
+            pixel.albedo += sample.albedo * scale;
+            pixel.uv.0 += sample.uv.0 * scale;
+            pixel.uv.1 += sample.uv.1 * scale;
+        }
+        pixel
+    }
+}
+
+impl Aovs {
+    pub fn new(width: usize, height: usize) -> Self {
+        let pixels = width * height;
+        Self {
+            width,
+            height,
+            depth: vec![0.; pixels],
+            normal: vec![Vec3::default(); pixels],
+            albedo: vec![Color::default(); pixels],
+            uv: vec![(0., 0.); pixels],
+            object_id: vec![0; pixels],
+        }
+    }
+
+    pub fn set_pixel(&mut self, index: usize, pixel: &AovSample) {
+        self.depth[index] = pixel.depth;
+        self.normal[index] = pixel.normal;
+        self.albedo[index] = pixel.albedo;
+        self.uv[index] = pixel.uv;
+        self.object_id[index] = pixel.object_id;
+    }
+
+    /// Writes every AOV to its own image named `{prefix}_{aov}.png`. Depth is a 16 bit grayscale
+    /// image scaled to the farthest depth in the frame, normals are mapped from [-1,1] to [0,1]
+    /// and object IDs get a random color each.
+    pub fn save(&self, prefix: &str) -> ImageResult<()> {
+        let (width, height) = (self.width as u32, self.height as u32);
+        let pixel = |x: u32, y: u32| (y * width + x) as usize;
+
+        let max_depth = self.depth.iter().fold(0., |m: f64, d| m.max(*d));
+        let scale = if max_depth > 0. { 1. / max_depth } else { 0. };
+        let depth = ImageBuffer::from_fn(width, height, |x, y| {
+            let d = Interval::new(0., 1.).clamp(self.depth[pixel(x, y)] * scale);
+            Luma([(d * u16::MAX as f64).round() as u16])
+        });
+        depth.save(format!("{}_depth.png", prefix))?;
+
+        let normal = RgbImage::from_fn(width, height, |x, y| {
+            let n = self.normal[pixel(x, y)];
+            if n == Vec3::default() {
+                return Rgb([0, 0, 0]);
+            }
+            let n = (n + Vec3::new(1., 1., 1.)) * 0.5;
+            Rgb([to_byte(n.x()), to_byte(n.y()), to_byte(n.z())])
+        });
+        normal.save(format!("{}_normal.png", prefix))?;
+
+        let albedo =
+            RgbImage::from_fn(
+                width,
+                height,
+                |x, y| Rgb(self.albedo[pixel(x, y)].get_rgb()),
+            );
+        albedo.save(format!("{}_albedo.png", prefix))?;
+
+        let uv = RgbImage::from_fn(width, height, |x, y| {
+            let (u, v) = self.uv[pixel(x, y)];
+            Rgb([to_byte(u), to_byte(v), 0])
+        });
+        uv.save(format!("{}_uv.png", prefix))?;
+
+        let object_id =
+            RgbImage::from_fn(width, height, |x, y| match self.object_id[pixel(x, y)] {
+                0 => Rgb([0, 0, 0]),
+                id => {
+                    let [r, g, b, ..] = sample_seed(id as u64, 0, 0).to_le_bytes();
+                    Rgb([r, g, b])
+                }
+            });
+        object_id.save(format!("{}_id.png", prefix))
+    }
+}
+
+// Linear value in [0,1] to a byte, without the gamma of Color::get_rgb
+fn to_byte(x: f64) -> u8 {
+    (256. * Interval::new(0., 0.999).clamp(x)) as u8
+}
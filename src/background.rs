@@ -0,0 +1,44 @@
+use serde::Serialize;
+
+use crate::{color::Color, ray::Ray, vec3::unit_vector};
+
+/// What a ray sees when it escapes the scene without hitting anything
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// No environment light at all, the scene is only lit by emissive materials
+    None,
+    Solid(Color),
+    /// Vertical blend from `bottom` (looking straight down) to `top` (looking straight up)
+    Gradient {
+        top: Color,
+        bottom: Color,
+    },
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+impl Background {
+    /// The white-to-blue sky used by the original scenes
+    pub fn sky() -> Self {
+        Self::Gradient {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: Color::new(1., 1., 1.),
+        }
+    }
+
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Background::None => Color::default(),
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let unit_direction = unit_vector(&r.direction());
+                let a = 0.5 * (unit_direction.y() + 1.0);
+                *bottom * (1. - a) + *top * a
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable, HittableAxisCompare, HittableList},
+    hittable::{hit_replacing, HitRecord, Hittable, HittableAxisCompare, HittableList},
     interval::Interval,
     ray::Ray,
 };
@@ -66,12 +66,12 @@ impl Hittable for BVHNode {
         if !self.bbox.hit(r, ray_t) {
             return false;
         }
-        let hit_left = self.left.hit(r, ray_t, rec);
+        let hit_left = hit_replacing(self.left.as_ref(), r, ray_t, rec);
 
         // If we know that the ray has hit the left bbox, then we don't need to search through the
         // entire Interval of the right bounding box.
         let right_interval = Interval::new(ray_t.min, if hit_left { rec.t } else { ray_t.max });
-        let hit_right = self.right.hit(r, right_interval, rec);
+        let hit_right = hit_replacing(self.right.as_ref(), r, right_interval, rec);
         hit_left || hit_right
     }
     fn bounding_box(&self) -> AABB {
@@ -1,30 +1,53 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    aov::{AovSample, Aovs},
+    background::Background,
     color::Color,
     hittable::{HitRecord, Hittable, HittableList},
     interval::Interval,
+    material::{ScatterKind, ScatterRecord},
+    pdf::PDF,
     ray::{Point3, Ray},
+    sampler::{self, square_to_disk, SamplerKind},
+    spectrum::{self, at_wavelengths, hero_wavelengths},
     sphere::hit_sphere,
-    utils::{degrees_to_radians, random_double},
-    vec3::{cross, unit_vector, Vec3},
+    tile::{make_tiles, RenderControl, RenderStatus, Tile, TileOrder, TileResult},
+    utils::{degrees_to_radians, random_double, DEFAULT_SEED},
+    vec3::{cross, dot, unit_vector, Vec3},
 };
 
 use rayon::prelude::*;
 
-use std::sync::Arc;
+use std::{
+    f64::consts::PI,
+    fmt,
+    sync::{mpsc, Arc},
+};
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Camera {
     pub image_width: i32,
     pub image_height: i32,
     pub samples_per_pixel: i32, // random sampling per pixel for antialiasing
-    pixel_samples_scale: f64,
-    pub max_depth: i32,   // ray bounce depth
-    pub vfov: f64,        // vertical view angle -> field of view
-    pub lookfrom: Point3, // point where camera is looking from
-    pub lookat: Point3,   // point where camera is looking at
-    pub vup: Vec3,        // rotation angle of camera
+    pub max_depth: i32,         // ray bounce depth
+    pub bounce_limits: BounceLimits,
+    pub russian_roulette_depth: i32, // bounces before paths may be terminated at random
+    pub seed: u64,                   // seed of the random streams used by every pixel sample
+    pub sampler: SamplerKind,        // distribution of the samples within each pixel
+    pub total_samples: Option<i32>,  // samples per pixel over all passes of a progressive render
+    pub adaptive_sampling: Option<AdaptiveSampling>, // None takes samples_per_pixel everywhere
+    pub tile_size: i32,              // width and height of the tiles rendered
+    pub tile_order: TileOrder,       // order the tiles are rendered in
+    pub spectral: bool,              // trace wavelengths instead of RGB, for dispersion
+    pub shutter_open: f64,           // camera rays get times between open and close,
+    pub shutter_close: f64,          // for motion blur
+    pub vfov: f64,                   // vertical view angle -> field of view
+    pub lookfrom: Point3,            // point where camera is looking from
+    pub lookat: Point3,              // point where camera is looking at
+    pub vup: Vec3,                   // rotation angle of camera
+    pub background: Background,      // scene color for rays that miss every object
+    pub projection: Projection,      // call initialize() after changing it
 
     u: Vec3, // camera frame basis vectors
     v: Vec3,
@@ -45,7 +68,9 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(
+    // Thin constructor that takes its arguments as they are, CameraBuilder starts from it and
+    // checks the settings
+    pub(crate) fn new(
         image_width: i32,
         aspect_ratio: f64,
         samples_per_pixel: i32,
@@ -60,138 +85,601 @@ impl Camera {
         let mut image_height = (image_width as f64 / aspect_ratio) as i32;
         image_height = if image_height < 1 { 1 } else { image_height };
 
-        let pixel_samples_scale = 1. / samples_per_pixel as f64;
+        let mut camera = Self {
+            image_width,
+            image_height,
+            lookfrom,
+            lookat,
+            vup,
+            background: Background::default(),
+            projection: Projection::default(),
+            u: Vec3::default(),
+            v: Vec3::default(),
+            w: Vec3::default(),
+            samples_per_pixel,
+            max_depth,
+            bounce_limits: BounceLimits::default(),
+            russian_roulette_depth: 5,
+            seed: DEFAULT_SEED,
+            sampler: SamplerKind::default(),
+            total_samples: None,
+            adaptive_sampling: None,
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            spectral: false,
+            shutter_open: 0.,
+            shutter_close: 1.,
+            vfov,
+            defocus_angle,
+            focus_dist,
+            viewport_width: 0.,
+            viewport_height: 0.,
+            viewport_u: Vec3::default(),
+            viewport_v: Vec3::default(),
+            pixel_delta_u: Vec3::default(),
+            pixel_delta_v: Vec3::default(),
+            viewport_upper_left: Vec3::default(),
+            pixel00_loc: Vec3::default(),
+            defocus_disk_u: Vec3::default(),
+            defocus_disk_v: Vec3::default(),
+        };
+        camera.initialize();
+        camera
+    }
+
+    pub fn builder() -> CameraBuilder {
+        CameraBuilder::default()
+    }
+
+    /// Recomputes the camera frame and viewport, call it after changing the image size, the
+    /// placement, the lens or the projection
+    pub fn initialize(&mut self) {
         // Camera
-        let camera_center = lookfrom;
-        let w = unit_vector(&(lookfrom - lookat)); // z-axis, the directional vector that
-                                                   // looks at the object
-        let u = unit_vector(&cross(vup, w)); // the x axis of the camera looking
-                                             // at object
+        let camera_center = self.lookfrom;
+        // z-axis, the directional vector that looks at the object
+        let w = unit_vector(&(self.lookfrom - self.lookat));
+        // the x axis of the camera looking at object
+        let u = unit_vector(&cross(self.vup, w));
         let v = cross(w, u); // y-axis
 
         // Viewport dimensions
-        let theta = degrees_to_radians(vfov);
-        let h = f64::tan(theta / 2.);
-        let viewport_height = 2. * h * focus_dist;
-        let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
+        let viewport_height = match self.projection {
+            Projection::Orthographic { height } => height,
+            // Panoramic projections do not use the viewport
+            _ => {
+                let theta = degrees_to_radians(self.vfov);
+                let h = f64::tan(theta / 2.);
+                2. * h * self.focus_dist
+            }
+        };
+        let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges
         let viewport_u = u * viewport_width;
         let viewport_v = -v * viewport_height;
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel
-        let pixel_delta_u = viewport_u / image_width as f64;
-        let pixel_delta_v = viewport_v / image_height as f64;
+        let pixel_delta_u = viewport_u / self.image_width as f64;
+        let pixel_delta_v = viewport_v / self.image_height as f64;
 
         // Calculate location of the upper left pixel
         let viewport_upper_left =
-            camera_center - (w * focus_dist) - (viewport_u / 2.) - (viewport_v / 2.);
-        let pixel00_loc = viewport_upper_left + (pixel_delta_u * pixel_delta_v) * 0.5;
+            camera_center - (w * self.focus_dist) - (viewport_u / 2.) - (viewport_v / 2.);
+        let pixel00_loc = viewport_upper_left + (pixel_delta_u + pixel_delta_v) * 0.5;
 
         // Calculate the camera defocus disk basis vectors
-        let defocus_radius = focus_dist * f64::tan(degrees_to_radians(defocus_angle / 2.));
-        let defocus_disk_u = u * defocus_radius;
-        let defocus_disk_v = v * defocus_radius;
-        Self {
-            image_width,
-            image_height,
-            lookfrom,
-            lookat,
-            vup,
-            u,
-            v,
-            w,
-            samples_per_pixel,
-            max_depth,
-            vfov,
-            pixel_samples_scale,
-            defocus_angle,
-            focus_dist,
-            viewport_width,
-            viewport_height,
-            viewport_u,
-            viewport_v,
-            pixel_delta_u,
-            pixel_delta_v,
-            viewport_upper_left,
-            pixel00_loc,
-            defocus_disk_u,
-            defocus_disk_v,
-        }
+        let defocus_radius =
+            self.focus_dist * f64::tan(degrees_to_radians(self.defocus_angle / 2.));
+
+        self.u = u;
+        self.v = v;
+        self.w = w;
+        self.viewport_width = viewport_width;
+        self.viewport_height = viewport_height;
+        self.viewport_u = viewport_u;
+        self.viewport_v = viewport_v;
+        self.pixel_delta_u = pixel_delta_u;
+        self.pixel_delta_v = pixel_delta_v;
+        self.viewport_upper_left = viewport_upper_left;
+        self.pixel00_loc = pixel00_loc;
+        self.defocus_disk_u = u * defocus_radius;
+        self.defocus_disk_v = v * defocus_radius;
+    }
+
+    /// `lights` are the objects sampled directly at every diffuse bounce (next event
+    /// estimation). They must also be part of `world`; an empty list disables light sampling.
+    pub fn render(&self, world: &Arc<dyn Hittable>, lights: &HittableList) -> Vec<Color> {
+        self.render_samples(world, lights, 0)
     }
 
-    pub fn render(&self, world: &Arc<dyn Hittable>) -> Vec<Color> {
-        return (0..self.image_height)
+    /// Renders samples `first_sample..first_sample + samples_per_pixel` of every pixel, so
+    /// progressive renders can continue where the previous pass stopped. Each sample draws its
+    /// random numbers from its own stream and the samples of a pixel are added up in order, so
+    /// the result is the same however rayon spreads the work over threads.
+    pub fn render_samples(
+        &self,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+        first_sample: u64,
+    ) -> Vec<Color> {
+        self.render_pixels(world, lights, first_sample)
+            .into_iter()
+            .map(|(color, _)| color)
+            .collect()
+    }
+
+    /// Also returns how many samples every pixel took, which only varies with adaptive
+    /// sampling. See `sample_count_heatmap` to look at them.
+    pub fn render_with_sample_counts(
+        &self,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+    ) -> (Vec<Color>, Vec<u32>) {
+        self.render_pixels(world, lights, 0).into_iter().unzip()
+    }
+
+    fn render_pixels(
+        &self,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+        first_sample: u64,
+    ) -> Vec<(Color, u32)> {
+        let mut pixels = vec![(Color::default(), 0); self.image_width() * self.image_height()];
+        self.render_tiles(
+            world,
+            lights,
+            &self.tiles(),
+            first_sample,
+            &RenderControl::new(),
+            |result| {
+                let values: Vec<(Color, u32)> = result
+                    .pixels
+                    .into_iter()
+                    .zip(result.sample_counts)
+                    .collect();
+                result
+                    .tile
+                    .copy_into(&values, &mut pixels, self.image_width);
+            },
+        );
+        pixels
+    }
+
+    /// Renders the AOVs of the first surfaces seen through every pixel, using the same camera
+    /// samples as `render`. This is a separate pass that only traces camera rays, so it costs
+    /// about one bounce per sample on top of the color render.
+    pub fn render_aovs(&self, world: &Arc<dyn Hittable>) -> Aovs {
+        self.render_aovs_with_samples(world, self.samples_per_pixel as u32)
+    }
+
+    /// Renders the AOVs with the first `samples` camera samples of every pixel
+    pub fn render_aovs_with_samples(&self, world: &Arc<dyn Hittable>, samples: u32) -> Aovs {
+        let pixels: Vec<AovSample> = (0..self.image_height)
             .into_par_iter()
             .flat_map(|j| {
-                let row: Vec<Color> = (0..self.image_width)
-                    .into_par_iter()
+                let row: Vec<AovSample> = (0..self.image_width)
                     .map(|i| {
-                        let pixel_color: Color = (0..self.samples_per_pixel)
-                            .into_par_iter() // Make this parallel too
-                            .map(|_| {
-                                let r = self.get_ray(i, j);
-                                self.ray_color(r, world, self.max_depth)
-                            })
-                            .reduce(|| Color::default(), |acc, color| acc + color);
-                        pixel_color * self.pixel_samples_scale
+                        let pixel = (j as u64) * (self.image_width as u64) + i as u64;
+                        let samples: Vec<AovSample> =
+                            (0..samples as u64)
+                                .map(|index| {
+                                    self.with_sample(pixel, index, self.samples_per_pixel, || {
+                                        match self.get_ray(i, j) {
+                                            Some(r) => self.aov_sample(r, world),
+                                            None => AovSample::default(),
+                                        }
+                                    })
+                                })
+                                .collect();
+                        AovSample::average(&samples)
                     })
                     .collect();
                 row
             })
             .collect();
-    }
 
-    pub fn ray_color(&self, ray: Ray, world: &Arc<dyn Hittable>, depth: i32) -> Color {
-        if depth <= 0 {
-            return Color::default();
+        let mut aovs = Aovs::new(self.image_width(), self.image_height());
+        for (index, pixel) in pixels.iter().enumerate() {
+            aovs.set_pixel(index, pixel);
         }
-        let mut rec: HitRecord = Default::default();
+        aovs
+    }
 
-        // Fix for shadow acne, due to floating point rounding errors, the reflected ray might end
-        // up being under surface of the object, we limit the minimum intersect distance
-        if world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
-            // let direction = Vec3::random_on_hemisphere(*rec.normal); --- Uniform Reflection
-            // let direction = rec.normal + Vec3::random_unit_vector(); // Lambertian Reflection
-            let mut scattered = Ray::default();
-            let mut attenuation = Color::default();
-            if rec
+    fn aov_sample(&self, ray: Ray, world: &Arc<dyn Hittable>) -> AovSample {
+        let mut rec = HitRecord::default();
+        if !world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
+            return AovSample::default();
+        }
+        AovSample {
+            // w points from the scene to the camera
+            depth: dot(self.lookfrom - rec.p, self.w),
+            normal: rec.normal,
+            albedo: rec
                 .material
                 .as_ref()
-                .unwrap()
-                .scatter(&ray, &rec, &mut attenuation, &mut scattered)
-            {
-                return attenuation * self.ray_color(scattered, world, depth - 1);
+                .map_or(Color::default(), |material| material.albedo(&rec)),
+            uv: (rec.u, rec.v),
+            object_id: rec.object_id,
+        }
+    }
+
+    /// The tiles of the image, in the order they are rendered
+    pub fn tiles(&self) -> Vec<Tile> {
+        make_tiles(
+            self.image_width,
+            self.image_height,
+            self.tile_size,
+            self.tile_order,
+        )
+    }
+
+    /// Renders `tiles` in parallel, calling `on_tile` on the calling thread as each one finishes
+    /// (or, when called from a rayon worker, once all of them are done).
+    /// Tiles are started in the given order; once `control` is cancelled or runs out of time
+    /// the remaining ones are skipped. Samples are numbered like in `render_samples`.
+    pub fn render_tiles(
+        &self,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+        tiles: &[Tile],
+        first_sample: u64,
+        control: &RenderControl,
+        mut on_tile: impl FnMut(TileResult),
+    ) -> RenderStatus {
+        let mut finished = 0;
+        if rayon::current_thread_index().is_some() {
+            // A worker of the pool must not block waiting for the tiles, it may be the only
+            // thread there is to render them. The tiles are rendered first and handed to
+            // `on_tile` once they are all done.
+            let results: Vec<Option<TileResult>> = tiles
+                .par_iter()
+                .map(|&tile| {
+                    (!control.should_stop())
+                        .then(|| self.render_tile(tile, world, lights, first_sample))
+                })
+                .collect();
+            for result in results.into_iter().flatten() {
+                finished += 1;
+                on_tile(result);
+            }
+        } else {
+            let (sender, receiver) = mpsc::channel();
+            // Spawned tiles only borrow the camera and the scene, while the calling thread
+            // collects the results so `on_tile` does not need to be Send
+            rayon::in_place_scope_fifo(|scope| {
+                for &tile in tiles {
+                    let sender = sender.clone();
+                    scope.spawn_fifo(move |_| {
+                        if control.should_stop() {
+                            return;
+                        }
+                        let _ = sender.send(self.render_tile(tile, world, lights, first_sample));
+                    });
+                }
+                drop(sender);
+                for result in receiver {
+                    finished += 1;
+                    on_tile(result);
+                }
+            });
+        }
+
+        if finished == tiles.len() {
+            RenderStatus::Finished
+        } else if control.is_cancelled() {
+            RenderStatus::Cancelled
+        } else {
+            RenderStatus::OutOfTime
+        }
+    }
+
+    fn render_tile(
+        &self,
+        tile: Tile,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+        first_sample: u64,
+    ) -> TileResult {
+        let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+        let mut sample_counts = Vec::with_capacity(pixels.capacity());
+        for j in tile.y..tile.y + tile.height {
+            for i in tile.x..tile.x + tile.width {
+                let (color, samples) = self.sample_pixel(i, j, world, lights, first_sample);
+                pixels.push(color);
+                sample_counts.push(samples);
+            }
+        }
+        TileResult {
+            tile,
+            pixels,
+            sample_counts,
+        }
+    }
+
+    // Runs `f` as sample `index` of `pixel`, out of the `samples_per_pixel` of this pass or
+    // out of total_samples when the render is split into passes
+    fn with_sample<T>(
+        &self,
+        pixel: u64,
+        index: u64,
+        samples_per_pixel: i32,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let sample_count = self.total_samples.unwrap_or(samples_per_pixel);
+        sampler::with_sample(
+            self.sampler,
+            self.seed,
+            pixel,
+            index,
+            sample_count as u64,
+            f,
+        )
+    }
+
+    // Average color of pixel i, j and the number of samples it took
+    fn sample_pixel(
+        &self,
+        i: i32,
+        j: i32,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+        first_sample: u64,
+    ) -> (Color, u32) {
+        let (min_samples, max_samples) = match self.adaptive_sampling {
+            Some(adaptive) => {
+                let min_samples = i32::max(adaptive.min_samples, 1);
+                (min_samples, i32::max(adaptive.max_samples, min_samples))
+            }
+            None => (self.samples_per_pixel, self.samples_per_pixel),
+        };
+
+        let pixel = (j as u64) * (self.image_width as u64) + i as u64;
+        let mut pixel_color = Color::default();
+        let mut luminance = RunningVariance::default();
+        let mut samples = 0;
+        for sample in 0..max_samples as u64 {
+            let index = first_sample + sample;
+            let sample_color =
+                self.with_sample(pixel, index, max_samples, || match self.get_ray(i, j) {
+                    Some(r) if self.spectral => {
+                        let (hero, _) = spectrum::sample_visible_wavelength(sampler::get_1d());
+                        let radiance = self.ray_color(r.with_wavelength(hero), world, lights);
+                        spectrum::wavelength_sample_to_rgb(radiance, hero)
+                    }
+                    Some(r) => self.ray_color(r, world, lights),
+                    None => Color::default(),
+                });
+            pixel_color += sample_color;
+            samples += 1;
+
+            if let Some(adaptive) = self.adaptive_sampling {
+                luminance.add(
+                    0.2126 * sample_color.x()
+                        + 0.7152 * sample_color.y()
+                        + 0.0722 * sample_color.z(),
+                );
+                if sample + 1 >= min_samples as u64
+                    && luminance.display_error() <= adaptive.threshold
+                {
+                    break;
+                }
+            }
+        }
+        (pixel_color * (1. / samples as f64), samples)
+    }
+
+    pub fn ray_color(&self, ray: Ray, world: &Arc<dyn Hittable>, lights: &HittableList) -> Color {
+        let mut ray = ray;
+        let mut color = Color::default();
+        // Fraction of the light arriving along the current ray that reaches the camera
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut bounces = BounceCounts::default();
+        // Density the last bounce sampled the current ray with, when that bounce also sampled
+        // the lights directly. Light found by this ray could then have been found by either
+        // strategy, so it is weighted with multiple importance sampling.
+        let mut bsdf_pdf: Option<f64> = None;
+        // Every ray of the path keeps the hero wavelength of the camera ray
+        let wavelength = ray.wavelength();
+        let wavelengths = hero_wavelengths(wavelength);
+        let mut secondaries_terminated = false;
+
+        for depth in 0..self.max_depth {
+            let mut rec: HitRecord = Default::default();
+
+            // Fix for shadow acne, due to floating point rounding errors, the reflected ray might
+            // end up being under surface of the object, we limit the minimum intersect distance
+            if !world.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rec) {
+                // The ray hits nothing, add the background color
+                color += throughput * at_wavelengths(self.background.value(&ray), wavelengths);
+                break;
+            }
+
+            let material = rec.material.as_ref().unwrap();
+            let mut color_from_emission =
+                at_wavelengths(material.emitted(rec.u, rec.v, &rec.p), wavelengths);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if color_from_emission != Color::default() {
+                    let light_pdf = lights.pdf_value(&ray.origin(), &ray.direction());
+                    color_from_emission *= power_heuristic(bsdf_pdf, light_pdf);
+                }
+            }
+            color += throughput * color_from_emission;
+
+            let mut srec = ScatterRecord::default();
+            if !material.scatter(&ray, &rec, &mut srec) {
+                break;
+            }
+            srec.attenuation = at_wavelengths(srec.attenuation, wavelengths);
+            if srec.wavelength_dependent && wavelength > 0. && !secondaries_terminated {
+                // The companion wavelengths would leave in other directions, keep only the hero.
+                // wavelength_sample_to_rgb averages the three wavelengths, weighting each by
+                // 1 / (3 pdf); alone the hero is a full estimate of the spectrum that needs
+                // 1 / pdf, hence the 3. Heroes follow the same distribution as any one of the
+                // three, so this is as unbiased as before, only noisier.
+                throughput = Color::new(3. * throughput.x(), 0., 0.);
+                secondaries_terminated = true;
+            }
+            if !bounces.add(srec.kind, &self.bounce_limits) {
+                break;
             }
+
+            match &srec.pdf {
+                None => {
+                    // Specular bounce, the material already chose the only outgoing direction
+                    throughput = throughput * srec.attenuation;
+                    ray = srec.skip_pdf_ray.with_wavelength(wavelength);
+                    bsdf_pdf = None;
+                }
+                Some(pdf) => {
+                    // Next event estimation: shoot a shadow ray towards a point on the lights
+                    let sample_lights = !lights.objects.is_empty();
+                    if sample_lights {
+                        color += throughput
+                            * self.sample_lights(&ray, &rec, &srec, pdf.as_ref(), world, lights);
+                    }
+
+                    // Weight the sample by how likely the material is to scatter in the sampled
+                    // direction, over how likely we were to pick that direction
+                    let scattered =
+                        Ray::new_tm(rec.p, pdf.generate(), ray.time()).with_wavelength(wavelength);
+                    let pdf_value = pdf.value(&scattered.direction());
+                    if pdf_value <= 0. {
+                        break;
+                    }
+                    let scattering_pdf = material.scattering_pdf(&ray, &rec, &scattered);
+                    throughput = throughput * srec.attenuation * (scattering_pdf / pdf_value);
+                    ray = scattered;
+                    bsdf_pdf = sample_lights.then_some(pdf_value);
+                }
+            }
+
+            // Russian roulette: end paths that carry little light with some probability, and
+            // boost the survivors so the estimate stays unbiased
+            if depth + 1 >= self.russian_roulette_depth {
+                let survival = f64::min(
+                    1.,
+                    f64::max(throughput.x(), f64::max(throughput.y(), throughput.z())),
+                );
+                if survival <= 0. || random_double() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+        }
+        color
+    }
+
+    // Light arriving directly from the lights through a single shadow ray, weighted against
+    // finding the same light by sampling the material
+    fn sample_lights(
+        &self,
+        ray: &Ray,
+        rec: &HitRecord,
+        srec: &ScatterRecord,
+        bsdf: &dyn PDF,
+        world: &Arc<dyn Hittable>,
+        lights: &HittableList,
+    ) -> Color {
+        let material = rec.material.as_ref().unwrap();
+        let light_ray = Ray::new_tm(rec.p, lights.random(&rec.p), ray.time());
+        let light_pdf = lights.pdf_value(&rec.p, &light_ray.direction());
+        if light_pdf <= 0. {
+            return Color::default();
+        }
+        let scattering_pdf = material.scattering_pdf(ray, rec, &light_ray);
+        let mut light_rec = HitRecord::default();
+        if scattering_pdf <= 0.
+            || !world.hit(
+                &light_ray,
+                Interval::new(0.001, f64::INFINITY),
+                &mut light_rec,
+            )
+        {
             return Color::default();
         }
 
-        let unit_direction = unit_vector(&ray.direction());
-        let a = 0.5 * (unit_direction.y() + 1.0);
-        return Color::new(1., 1., 1.) * (1. - a) + Color::new(0.5, 0.7, 1.) * a;
+        // Whatever is hit first decides how much light arrives, occluders emit nothing
+        let light_material = light_rec.material.as_ref().unwrap();
+        let emitted = at_wavelengths(
+            light_material.emitted(light_rec.u, light_rec.v, &light_rec.p),
+            hero_wavelengths(ray.wavelength()),
+        );
+        let weight = power_heuristic(light_pdf, bsdf.value(&light_ray.direction()));
+        srec.attenuation * emitted * (scattering_pdf * weight / light_pdf)
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
+    // None for samples that fall outside the image circle of a fisheye projection
+    fn get_ray(&self, i: i32, j: i32) -> Option<Ray> {
         // Construct a camera ray originating from the defocus disk, and directed at a randomly
         // sampled point around the pixel location i, j
         let offset = sample_square();
         let pixel_sample = self.pixel00_loc
             + (self.pixel_delta_u * (offset.x() + i as f64))
             + (self.pixel_delta_v * (offset.y() + j as f64));
-        let ray_origin = if self.defocus_angle <= 0. {
-            self.lookfrom
-        } else {
-            self.defocus_disk_sample()
+        let (ray_origin, ray_direction) = match self.projection {
+            Projection::Perspective => {
+                let ray_origin = if self.defocus_angle <= 0. {
+                    self.lookfrom
+                } else {
+                    self.defocus_disk_sample()
+                };
+                (ray_origin, pixel_sample - ray_origin)
+            }
+            // Parallel rays starting from the plane through lookfrom, there is no lens to blur
+            Projection::Orthographic { .. } => (pixel_sample + self.w * self.focus_dist, -self.w),
+            // Directions from the position of the sample in the image, in [0,1]
+            Projection::Equirectangular | Projection::Fisheye { .. } => {
+                let x = (i as f64 + 0.5 + offset.x()) / self.image_width as f64;
+                let y = (j as f64 + 0.5 + offset.y()) / self.image_height as f64;
+                (self.lookfrom, self.panoramic_direction(x, y)?)
+            }
         };
-        let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random_double();
-        return Ray::new_tm(ray_origin, ray_direction, ray_time);
+        let ray_time =
+            self.shutter_open + (self.shutter_close - self.shutter_open) * sampler::get_1d();
+        return Some(Ray::new_tm(ray_origin, ray_direction, ray_time));
+    }
+
+    fn panoramic_direction(&self, x: f64, y: f64) -> Option<Vec3> {
+        match self.projection {
+            Projection::Equirectangular => {
+                // Inverse of the texture coordinates of Sphere: x is the angle around v starting
+                // from -u, y the angle from straight up
+                let phi = 2. * PI * x - PI;
+                let theta = PI * y;
+                let (sin_theta, cos_theta) = f64::sin_cos(theta);
+                Some(
+                    (self.u * f64::cos(phi) - self.w * f64::sin(phi)) * sin_theta
+                        + self.v * cos_theta,
+                )
+            }
+            Projection::Fisheye { fov, mapping } => {
+                // Offset from the image centre, 1 at the edge of the circle inscribed in the image
+                let radius = f64::min(self.image_width as f64, self.image_height as f64) / 2.;
+                let px = (x - 0.5) * self.image_width as f64 / radius;
+                let py = (0.5 - y) * self.image_height as f64 / radius;
+                let r = f64::sqrt(px * px + py * py);
+                if r > 1. {
+                    return None;
+                }
+                // Angle from the viewing direction
+                let half_fov = degrees_to_radians(fov) / 2.;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half_fov,
+                    FisheyeMapping::Equisolid => 2. * f64::asin(r * f64::sin(half_fov / 2.)),
+                };
+                let (sin_theta, cos_theta) = f64::sin_cos(theta);
+                let (cos_phi, sin_phi) = if r > 0. { (px / r, py / r) } else { (1., 0.) };
+                Some((self.u * cos_phi + self.v * sin_phi) * sin_theta - self.w * cos_theta)
+            }
+            Projection::Perspective | Projection::Orthographic { .. } => None,
+        }
     }
 
     fn defocus_disk_sample(&self) -> Point3 {
         // Returns a random point in the camera defocus disk
-        let p = Vec3::random_in_unit_disk();
-        self.lookfrom + (self.defocus_disk_u * p[0]) + (self.defocus_disk_v * p[1])
+        let (u, v) = sampler::get_2d();
+        let (x, y) = square_to_disk(u, v);
+        self.lookfrom + (self.defocus_disk_u * x) + (self.defocus_disk_v * y)
     }
 
     pub fn image_width(&self) -> usize {
@@ -202,8 +690,452 @@ impl Camera {
     }
 }
 
+/// Sets up a Camera one setting at a time, starting from a 400 pixel wide 16:9 image with 100
+/// samples per pixel, looking down -z from the origin with a 90 degree field of view. `build`
+/// rejects settings that cannot give an image.
+#[derive(Clone)]
+pub struct CameraBuilder {
+    camera: Camera,
+    aspect_ratio: Option<f64>, // None keeps image_height
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        let camera = Camera::new(
+            400,
+            16. / 9.,
+            100,
+            50,
+            90.,
+            Point3::new(0., 0., 0.),
+            Point3::new(0., 0., -1.),
+            Vec3::new(0., 1., 0.),
+            0.,
+            10.,
+        );
+        Self {
+            camera,
+            aspect_ratio: Some(16. / 9.),
+        }
+    }
+}
+
+impl CameraBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from every setting of `camera`, including its image height
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            camera: camera.clone(),
+            aspect_ratio: None,
+        }
+    }
+
+    pub fn image_width(mut self, image_width: i32) -> Self {
+        self.camera.image_width = image_width;
+        self
+    }
+
+    /// Fixes the height in pixels, replacing any aspect ratio
+    pub fn image_height(mut self, image_height: i32) -> Self {
+        self.camera.image_height = image_height;
+        self.aspect_ratio = None;
+        self
+    }
+
+    /// Derives the height from the width, replacing any explicit height
+    pub fn aspect_ratio(mut self, aspect_ratio: f64) -> Self {
+        self.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    pub fn samples_per_pixel(mut self, samples_per_pixel: i32) -> Self {
+        self.camera.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: i32) -> Self {
+        self.camera.max_depth = max_depth;
+        self
+    }
+
+    pub fn bounce_limits(mut self, bounce_limits: BounceLimits) -> Self {
+        self.camera.bounce_limits = bounce_limits;
+        self
+    }
+
+    pub fn russian_roulette_depth(mut self, russian_roulette_depth: i32) -> Self {
+        self.camera.russian_roulette_depth = russian_roulette_depth;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.camera.seed = seed;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+        self.camera.sampler = sampler;
+        self
+    }
+
+    /// For renders made of several `render_samples` passes, the samples per pixel they add up
+    /// to, so that stratified samplers spread their strata over all of them
+    pub fn total_samples(mut self, total_samples: Option<i32>) -> Self {
+        self.camera.total_samples = total_samples;
+        self
+    }
+
+    pub fn adaptive_sampling(mut self, adaptive_sampling: Option<AdaptiveSampling>) -> Self {
+        self.camera.adaptive_sampling = adaptive_sampling;
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: i32) -> Self {
+        self.camera.tile_size = tile_size;
+        self
+    }
+
+    pub fn tile_order(mut self, tile_order: TileOrder) -> Self {
+        self.camera.tile_order = tile_order;
+        self
+    }
+
+    pub fn spectral(mut self, spectral: bool) -> Self {
+        self.camera.spectral = spectral;
+        self
+    }
+
+    pub fn shutter(mut self, open: f64, close: f64) -> Self {
+        self.camera.shutter_open = open;
+        self.camera.shutter_close = close;
+        self
+    }
+
+    pub fn vfov(mut self, vfov: f64) -> Self {
+        self.camera.vfov = vfov;
+        self
+    }
+
+    pub fn lookfrom(mut self, lookfrom: Point3) -> Self {
+        self.camera.lookfrom = lookfrom;
+        self
+    }
+
+    pub fn lookat(mut self, lookat: Point3) -> Self {
+        self.camera.lookat = lookat;
+        self
+    }
+
+    pub fn vup(mut self, vup: Vec3) -> Self {
+        self.camera.vup = vup;
+        self
+    }
+
+    pub fn background(mut self, background: Background) -> Self {
+        self.camera.background = background;
+        self
+    }
+
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.camera.projection = projection;
+        self
+    }
+
+    pub fn defocus_angle(mut self, defocus_angle: f64) -> Self {
+        self.camera.defocus_angle = defocus_angle;
+        self
+    }
+
+    pub fn focus_dist(mut self, focus_dist: f64) -> Self {
+        self.camera.focus_dist = focus_dist;
+        self
+    }
+
+    pub fn build(self) -> Result<Camera, CameraError> {
+        let mut camera = self.camera;
+        if let Some(aspect_ratio) = self.aspect_ratio {
+            if !(aspect_ratio.is_finite() && aspect_ratio > 0.) {
+                return Err(CameraError::InvalidAspectRatio(aspect_ratio));
+            }
+            camera.image_height = (camera.image_width as f64 / aspect_ratio) as i32;
+        }
+        if camera.image_width < 1 || camera.image_height < 1 {
+            return Err(CameraError::EmptyImage {
+                width: camera.image_width,
+                height: camera.image_height,
+            });
+        }
+        if camera.samples_per_pixel < 1 {
+            return Err(CameraError::NoSamples);
+        }
+        if camera.max_depth < 1 {
+            return Err(CameraError::InvalidMaxDepth(camera.max_depth));
+        }
+        if let Some(AdaptiveSampling {
+            min_samples,
+            max_samples,
+            ..
+        }) = camera.adaptive_sampling
+        {
+            if min_samples < 1 || min_samples > max_samples {
+                return Err(CameraError::InvalidAdaptiveSampling {
+                    min_samples,
+                    max_samples,
+                });
+            }
+        }
+        if camera.tile_size < 1 {
+            return Err(CameraError::InvalidTileSize(camera.tile_size));
+        }
+        if let Some(total) = camera.total_samples {
+            if total < camera.samples_per_pixel {
+                return Err(CameraError::InvalidTotalSamples(total));
+            }
+        }
+        let view = camera.lookat - camera.lookfrom;
+        if view.length_squared() == 0. {
+            return Err(CameraError::LookfromIsLookat);
+        }
+        let vup_length = camera.vup.length();
+        if vup_length == 0. || cross(unit_vector(&view), camera.vup / vup_length).length() < 1e-6 {
+            return Err(CameraError::VupParallelToView);
+        }
+        match camera.projection {
+            Projection::Perspective if !(camera.vfov > 0. && camera.vfov < 180.) => {
+                return Err(CameraError::InvalidFieldOfView(camera.vfov));
+            }
+            Projection::Fisheye { fov, .. } if !(fov > 0. && fov <= 360.) => {
+                return Err(CameraError::InvalidFieldOfView(fov));
+            }
+            Projection::Orthographic { height } if !(height > 0. && height.is_finite()) => {
+                return Err(CameraError::InvalidViewHeight(height));
+            }
+            _ => {}
+        }
+        if !(camera.focus_dist > 0. && camera.focus_dist.is_finite()) {
+            return Err(CameraError::InvalidFocusDistance(camera.focus_dist));
+        }
+        if !(camera.defocus_angle >= 0. && camera.defocus_angle < 180.) {
+            return Err(CameraError::InvalidDefocusAngle(camera.defocus_angle));
+        }
+        let (open, close) = (camera.shutter_open, camera.shutter_close);
+        if !(open.is_finite() && close.is_finite() && open <= close) {
+            return Err(CameraError::InvalidShutter { open, close });
+        }
+        camera.initialize();
+        Ok(camera)
+    }
+}
+
+/// Why CameraBuilder::build refused a configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraError {
+    EmptyImage { width: i32, height: i32 },
+    InvalidAspectRatio(f64),
+    NoSamples,
+    InvalidMaxDepth(i32),
+    InvalidAdaptiveSampling { min_samples: i32, max_samples: i32 },
+    InvalidTileSize(i32),
+    InvalidTotalSamples(i32), // fewer than samples_per_pixel
+    LookfromIsLookat,
+    VupParallelToView, // also when vup is zero
+    InvalidFieldOfView(f64),
+    InvalidViewHeight(f64),
+    InvalidFocusDistance(f64),
+    InvalidDefocusAngle(f64),
+    InvalidShutter { open: f64, close: f64 },
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CameraError::EmptyImage { width, height } => {
+                write!(f, "the image is {} by {} pixels", width, height)
+            }
+            CameraError::InvalidAspectRatio(ratio) => write!(f, "invalid aspect ratio {}", ratio),
+            CameraError::NoSamples => write!(f, "at least one sample per pixel is needed"),
+            CameraError::InvalidMaxDepth(depth) => write!(f, "invalid max depth {}", depth),
+            CameraError::InvalidAdaptiveSampling {
+                min_samples,
+                max_samples,
+            } => write!(
+                f,
+                "invalid adaptive sampling from {} to {} samples",
+                min_samples, max_samples
+            ),
+            CameraError::InvalidTileSize(size) => write!(f, "invalid tile size {}", size),
+            CameraError::InvalidTotalSamples(total) => {
+                write!(f, "{} total samples is fewer than one pass", total)
+            }
+            CameraError::LookfromIsLookat => write!(f, "lookfrom and lookat are the same point"),
+            CameraError::VupParallelToView => {
+                write!(f, "vup is zero or parallel to the viewing direction")
+            }
+            CameraError::InvalidFieldOfView(fov) => write!(f, "invalid field of view {}", fov),
+            CameraError::InvalidViewHeight(height) => write!(f, "invalid view height {}", height),
+            CameraError::InvalidFocusDistance(dist) => {
+                write!(f, "invalid focus distance {}", dist)
+            }
+            CameraError::InvalidDefocusAngle(angle) => {
+                write!(f, "invalid defocus angle {}", angle)
+            }
+            CameraError::InvalidShutter { open, close } => {
+                write!(f, "the shutter opens at {} and closes at {}", open, close)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+/// How camera rays leave the camera
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum Projection {
+    /// Rays fan out from lookfrom (or from the lens when defocus_angle is set), covering `vfov`
+    #[default]
+    Perspective,
+    /// Rays run parallel to the viewing direction, the view is `height` world units tall
+    Orthographic { height: f64 },
+    /// Every direction around lookfrom, longitude across the image and latitude down it, laid
+    /// out like the texture coordinates of Sphere so the image can be used as an environment
+    /// map. The viewing direction is at three quarters of the width, use a 2:1 aspect ratio.
+    Equirectangular,
+    /// Directions within `fov` degrees (up to 360) of the viewing direction, mapped to the
+    /// circle inscribed in the image. Pixels outside the circle are black.
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+}
+
+/// How the angle from the viewing direction grows with the distance from the centre of a
+/// fisheye image
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    /// Proportionally, so angles are measured evenly
+    #[default]
+    Equidistant,
+    /// Like the sine of half the angle, so every pixel covers the same solid angle
+    Equisolid,
+}
+
+/// Keeps sampling a pixel until the estimated error of its displayed value drops below
+/// `threshold`, taking between `min_samples` and `max_samples` samples
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: i32,
+    pub max_samples: i32,
+    pub threshold: f64, // error after gamma correction, 0.01 is about 2.5 of 255 levels
+}
+
+// Smallest difference from the mean that unseen samples are assumed to have, see display_error
+const MIN_OUTLIER: f64 = 0.1;
+
+// Welford's online algorithm for the mean and variance of the samples of a pixel
+#[derive(Default)]
+struct RunningVariance {
+    count: u64,
+    mean: f64,
+    m2: f64, // sum of squared differences from the mean
+}
+
+impl RunningVariance {
+    fn add(&mut self, x: f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    // Standard error of the mean, carried through the gamma curve of Color::get_rgb
+    // (d sqrt(x) = dx / 2 sqrt(x)) since noise shows more in dark pixels than in bright ones.
+    // Samples that all agree don't prove the pixel smooth, a small light may be found by one
+    // path in a thousand, so the variance is at least what one more sample differing from the
+    // mean by the mean (or by MIN_OUTLIER in dark pixels) would give: d² / (n + 1)
+    fn display_error(&self) -> f64 {
+        if self.count < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.count as f64;
+        let outlier = f64::max(self.mean, MIN_OUTLIER);
+        let variance = f64::max(self.m2 / (n - 1.), outlier * outlier / (n + 1.));
+        let standard_error = f64::sqrt(variance / n);
+        standard_error / (2. * f64::sqrt(f64::max(self.mean, 1e-4)))
+    }
+}
+
+/// Colors sample counts from blue (fewest) over green to red (most)
+pub fn sample_count_heatmap(counts: &[u32]) -> Vec<Color> {
+    let min = counts.iter().copied().min().unwrap_or(0) as f64;
+    let max = counts.iter().copied().max().unwrap_or(0) as f64;
+    counts
+        .iter()
+        .map(|&count| {
+            let t = if max > min {
+                (count as f64 - min) / (max - min)
+            } else {
+                0.
+            };
+            if t < 0.5 {
+                Color::new(0., 2. * t, 1. - 2. * t)
+            } else {
+                Color::new(2. * t - 1., 2. - 2. * t, 0.)
+            }
+        })
+        .collect()
+}
+
+/// Maximum number of bounces of each kind along a path. Paths are also limited to
+/// `Camera::max_depth` bounces in total.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct BounceLimits {
+    pub diffuse: i32,
+    pub specular: i32,
+    pub transmission: i32,
+}
+
+impl Default for BounceLimits {
+    fn default() -> Self {
+        Self {
+            diffuse: i32::MAX,
+            specular: i32::MAX,
+            transmission: i32::MAX,
+        }
+    }
+}
+
+#[derive(Default)]
+struct BounceCounts {
+    diffuse: i32,
+    specular: i32,
+    transmission: i32,
+}
+
+impl BounceCounts {
+    // Counts a bounce, returns false when it goes over the limit for its kind
+    fn add(&mut self, kind: ScatterKind, limits: &BounceLimits) -> bool {
+        let (count, limit) = match kind {
+            ScatterKind::Diffuse => (&mut self.diffuse, limits.diffuse),
+            ScatterKind::Specular => (&mut self.specular, limits.specular),
+            ScatterKind::Transmission => (&mut self.transmission, limits.transmission),
+        };
+        *count += 1;
+        *count <= limit
+    }
+}
+
+// Veach's power heuristic (beta = 2) for the sample drawn from the `f` density
+fn power_heuristic(f_pdf: f64, g_pdf: f64) -> f64 {
+    let (f, g) = (f_pdf * f_pdf, g_pdf * g_pdf);
+    if f + g <= 0. {
+        return 0.;
+    }
+    f / (f + g)
+}
+
 fn sample_square() -> Vec3 {
-    Vec3::new(random_double() - 0.5, random_double() - 0.5, 0.)
+    let (x, y) = sampler::get_2d();
+    Vec3::new(x - 0.5, y - 0.5, 0.)
 }
 
 #[cfg(test)]
@@ -211,4 +1143,329 @@ mod camera {
     //Test viewport calculations
     //Test pixel00 calculation
     //Test focal length calculation
+    use super::*;
+    use crate::{
+        material::{Dielectric, Ior, Lambertian},
+        sphere::Sphere,
+        utils::seed_random,
+    };
+
+    #[test]
+    fn renders_are_reproducible() {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0., 0., -1.),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let world: Arc<dyn Hittable> = Arc::new(world);
+        let lights = HittableList::new();
+        let mut camera = Camera::builder()
+            .image_width(8)
+            .aspect_ratio(1.)
+            .samples_per_pixel(4)
+            .max_depth(10)
+            .seed(7)
+            .build()
+            .unwrap();
+        let first = camera.render(&world, &lights);
+        assert_eq!(first, camera.render(&world, &lights));
+        camera.seed = 8;
+        assert_ne!(first, camera.render(&world, &lights));
+    }
+
+    #[test]
+    fn renders_leave_the_callers_random_stream_alone() {
+        let world: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Point3::new(0., 0., -1.),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let camera = Camera::builder()
+            .image_width(4)
+            .aspect_ratio(1.)
+            .samples_per_pixel(2)
+            .max_depth(10)
+            .build()
+            .unwrap();
+        // In a single thread pool the parallel iterators run every sample on the calling thread
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            seed_random(3);
+            let expected: Vec<f64> = (0..2).map(|_| random_double()).collect();
+            seed_random(3);
+            let first = random_double();
+            camera.render_aovs(&world);
+            assert_eq!(vec![first, random_double()], expected);
+        });
+    }
+
+    #[test]
+    fn renders_from_inside_the_thread_pool() {
+        // The worker of a single thread pool is the only one that can render the tiles, it
+        // used to wait for them forever
+        let world: Arc<dyn Hittable> = Arc::new(Sphere::new(
+            Point3::new(0., 0., -1.),
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ));
+        let lights = HittableList::new();
+        let camera = Camera::builder()
+            .image_width(8)
+            .aspect_ratio(1.)
+            .samples_per_pixel(2)
+            .tile_size(4)
+            .build()
+            .unwrap();
+        let expected = camera.render(&world, &lights);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        assert_eq!(pool.install(|| camera.render(&world, &lights)), expected);
+    }
+
+    #[test]
+    fn hero_only_paths_match_the_rgb_render() {
+        // Cauchy with b = 0 refracts every wavelength alike but still takes the dispersive path
+        let glass = |ior| {
+            let mut world = HittableList::new();
+            world.add(Arc::new(Sphere::new(
+                Point3::new(0., 0., -1.),
+                0.5,
+                Arc::new(Dielectric::with_ior(ior)),
+            )));
+            world.add(Arc::new(Sphere::new(
+                Point3::new(0., -100.5, -1.),
+                100.,
+                Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1))),
+            )));
+            let world: Arc<dyn Hittable> = Arc::new(world);
+            world
+        };
+        let mut camera = Camera::builder()
+            .image_width(4)
+            .aspect_ratio(1.)
+            .samples_per_pixel(1024)
+            .max_depth(10)
+            .vfov(40.)
+            .lookfrom(Point3::new(0., 0., 1.))
+            .build()
+            .unwrap();
+        let lights = HittableList::new();
+        let mean = |pixels: Vec<Color>| {
+            pixels.iter().fold(Color::default(), |sum, &p| sum + p) * (1. / pixels.len() as f64)
+        };
+        let rgb = mean(camera.render(&glass(Ior::Constant(1.5)), &lights));
+        camera.spectral = true;
+        let spectral = mean(camera.render(&glass(Ior::Cauchy { a: 1.5, b: 0. }), &lights));
+        for channel in 0..3 {
+            assert!((spectral[channel] - rgb[channel]).abs() < 0.03 * rgb[channel]);
+        }
+    }
+
+    #[test]
+    fn progressive_passes_cover_the_strata() {
+        let camera = Camera::builder()
+            .image_width(1)
+            .aspect_ratio(1.)
+            .samples_per_pixel(1)
+            .total_samples(Some(4))
+            .sampler(SamplerKind::Stratified)
+            .projection(Projection::Orthographic { height: 1. })
+            .build()
+            .unwrap();
+        // One sample per pass, each in another quarter of the pixel
+        let mut cells: Vec<(bool, bool)> = (0..4)
+            .map(|pass| {
+                camera.with_sample(0, pass, camera.samples_per_pixel, || {
+                    let origin = camera.get_ray(0, 0).unwrap().origin();
+                    (origin.x() > 0., origin.y() > 0.)
+                })
+            })
+            .collect();
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 4);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = Camera::builder()
+            .image_width(4)
+            .aspect_ratio(2.)
+            .samples_per_pixel(1)
+            .lookfrom(Point3::new(0., 0., 5.))
+            .lookat(Point3::new(0., 0., 0.))
+            .projection(Projection::Orthographic { height: 2. })
+            .build()
+            .unwrap();
+        sampler::start_sample(SamplerKind::Independent, 0, 0, 0, 1);
+        let first = camera.get_ray(0, 0).unwrap();
+        let last = camera.get_ray(3, 1).unwrap();
+        assert_eq!(first.direction(), Vec3::new(0., 0., -1.));
+        assert_eq!(last.direction(), Vec3::new(0., 0., -1.));
+        // Rays start in the plane of lookfrom, within half a pixel of the centres of the corner
+        // pixels of the 4 by 2 view, whose pixels are 1 wide
+        for (r, centre) in [(first, (-1.5, 0.5)), (last, (1.5, -0.5))] {
+            assert_eq!(r.origin().z(), 5.);
+            assert!((r.origin().x() - centre.0).abs() <= 0.5);
+            assert!((r.origin().y() - centre.1).abs() <= 0.5);
+        }
+    }
+
+    #[test]
+    fn panoramic_directions() {
+        let mut camera = Camera::builder()
+            .image_width(200)
+            .aspect_ratio(2.)
+            .samples_per_pixel(1)
+            .projection(Projection::Equirectangular)
+            .build()
+            .unwrap();
+        let close = |a: Vec3, b: Vec3| (a - b).length() < 1e-9;
+        // The same layout as the texture coordinates of a sphere, see Sphere::get_sphere
+        assert!(close(
+            camera.panoramic_direction(0.75, 0.5).unwrap(),
+            Vec3::new(0., 0., -1.)
+        ));
+        assert!(close(
+            camera.panoramic_direction(0.5, 0.5).unwrap(),
+            Vec3::new(1., 0., 0.)
+        ));
+        assert!(close(
+            camera.panoramic_direction(0.25, 0.5).unwrap(),
+            Vec3::new(0., 0., 1.)
+        ));
+        assert!(close(
+            camera.panoramic_direction(0.3, 0.).unwrap(),
+            Vec3::new(0., 1., 0.)
+        ));
+
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            camera.projection = Projection::Fisheye { fov: 180., mapping };
+            assert!(close(
+                camera.panoramic_direction(0.5, 0.5).unwrap(),
+                Vec3::new(0., 0., -1.)
+            ));
+            // The edge of the circle is 90 degrees away, past it there is nothing
+            assert!(close(
+                camera.panoramic_direction(0.75, 0.5).unwrap(),
+                Vec3::new(1., 0., 0.)
+            ));
+            assert!(close(
+                camera.panoramic_direction(0.5, 0.).unwrap(),
+                Vec3::new(0., 1., 0.)
+            ));
+            assert!(camera.panoramic_direction(0.8, 0.5).is_none());
+        }
+    }
+
+    #[test]
+    fn builder_rejects_degenerate_cameras() {
+        let camera = Camera::builder().image_width(200).build().unwrap();
+        assert_eq!((camera.image_width, camera.image_height), (200, 112));
+        let camera = Camera::builder().image_height(50).build().unwrap();
+        assert_eq!((camera.image_width, camera.image_height), (400, 50));
+
+        let builder = CameraBuilder::from_camera(&camera);
+        let error = |builder: CameraBuilder| builder.build().err();
+        assert_eq!(error(builder.clone()), None);
+        assert_eq!(
+            error(builder.clone().image_width(0)),
+            Some(CameraError::EmptyImage {
+                width: 0,
+                height: 50
+            })
+        );
+        assert_eq!(
+            error(builder.clone().aspect_ratio(0.)),
+            Some(CameraError::InvalidAspectRatio(0.))
+        );
+        assert_eq!(
+            error(builder.clone().lookat(Point3::new(0., 0., 0.))),
+            Some(CameraError::LookfromIsLookat)
+        );
+        assert_eq!(
+            error(builder.clone().vup(Vec3::new(0., 0., 2.))),
+            Some(CameraError::VupParallelToView)
+        );
+        assert_eq!(
+            error(builder.clone().vfov(180.)),
+            Some(CameraError::InvalidFieldOfView(180.))
+        );
+        assert_eq!(
+            error(builder.clone().shutter(1., 0.)),
+            Some(CameraError::InvalidShutter {
+                open: 1.,
+                close: 0.
+            })
+        );
+        assert_eq!(
+            error(builder.clone().max_depth(0)),
+            Some(CameraError::InvalidMaxDepth(0))
+        );
+        assert_eq!(
+            error(builder.clone().tile_size(0)),
+            Some(CameraError::InvalidTileSize(0))
+        );
+        assert_eq!(
+            error(builder.clone().adaptive_sampling(Some(AdaptiveSampling {
+                min_samples: 64,
+                max_samples: 16,
+                threshold: 0.01,
+            }))),
+            Some(CameraError::InvalidAdaptiveSampling {
+                min_samples: 64,
+                max_samples: 16
+            })
+        );
+        assert_eq!(
+            error(builder.focus_dist(0.)),
+            Some(CameraError::InvalidFocusDistance(0.))
+        );
+    }
+
+    #[test]
+    fn adaptive_sampling_stops_early_on_smooth_pixels() {
+        let mut world = HittableList::new();
+        world.add(Arc::new(Sphere::new(
+            Point3::new(0., -100.5, -1.),
+            100.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        )));
+        let world: Arc<dyn Hittable> = Arc::new(world);
+        let camera = Camera::builder()
+            .image_width(8)
+            .aspect_ratio(1.)
+            .max_depth(10)
+            .adaptive_sampling(Some(AdaptiveSampling {
+                min_samples: 4,
+                max_samples: 256,
+                threshold: 0.01,
+            }))
+            .build()
+            .unwrap();
+        let (_, counts) = camera.render_with_sample_counts(&world, &HittableList::new());
+        // The top row only sees the smooth sky, pixels on the horizon mix sky and ground
+        let sky = *counts[..8].iter().max().unwrap();
+        assert!(sky < 64);
+        assert!(counts[32..40].iter().any(|&count| count > sky));
+        assert!(counts.iter().all(|&count| (4..=256).contains(&count)));
+    }
+
+    #[test]
+    fn identical_samples_are_not_converged() {
+        // A black pixel whose first samples all missed a small light, and a flat bright one
+        for value in [0., 0.8] {
+            let mut luminance = RunningVariance::default();
+            for _ in 0..16 {
+                luminance.add(value);
+            }
+            assert!(luminance.display_error() > 0.01);
+        }
+    }
 }
@@ -0,0 +1,207 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    cylinder::azimuth_uv,
+    disk::disk_bounding_box,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    onb::ONB,
+    ray::{Point3, Ray},
+    vec3::{unit_vector, Vec3},
+};
+
+/// Closed cone from a circular base up to an apex, capped with a disk at the base
+#[derive(Debug)]
+pub struct Cone {
+    base: Point3,
+    height: f64,
+    radius: f64,
+    frame: ONB, // w points from the base towards the apex
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl Cone {
+    /// A cone with the apex on the base or no radius has nothing to hit and an empty box
+    pub fn new(base: Point3, apex: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let axis = apex - base;
+        let radius = f64::max(0., radius);
+        if axis.near_zero() || radius == 0. {
+            return Self {
+                base,
+                height: 0.,
+                radius: 0.,
+                frame: ONB::new(&Vec3::new(0., 0., 1.)),
+                material: Some(material),
+                bbox: AABB::empty(),
+            };
+        }
+        Self {
+            base,
+            height: axis.length(),
+            radius,
+            frame: ONB::new(&axis),
+            material: Some(material),
+            bbox: AABB::with_boxes(
+                &disk_bounding_box(&base, &axis, radius),
+                &AABB::with_points(&apex, &apex),
+            ),
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if self.radius <= 0. {
+            return false;
+        }
+        // Intersect in the cone's local frame, where the base is centered on the origin and the
+        // apex is at z = height
+        let o = self.frame.to_local(&(r.origin() - self.base));
+        let d = self.frame.to_local(&r.direction());
+        let (radius, height) = (self.radius, self.height);
+        let k2 = (radius / height) * (radius / height);
+
+        let mut closest = ray_t.max;
+        let mut found: Option<(Vec3, f64, f64)> = None; // local normal, u, v
+
+        // Side: x^2 + y^2 = k^2 (height - z)^2 with 0 <= z <= height
+        let hz = height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let h = -(o.x() * d.x() + o.y() * d.y() + k2 * hz * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * hz * hz;
+        let roots = if f64::abs(a) < 1e-12 {
+            // Ray parallel to the slope of the cone, only one intersection
+            if f64::abs(h) < 1e-12 {
+                vec![]
+            } else {
+                vec![c / (2. * h)]
+            }
+        } else {
+            let discriminant = h * h - a * c;
+            if discriminant < 0. {
+                vec![]
+            } else {
+                let sqrtd = f64::sqrt(discriminant);
+                let (r1, r2) = ((h - sqrtd) / a, (h + sqrtd) / a);
+                vec![f64::min(r1, r2), f64::max(r1, r2)]
+            }
+        };
+        for root in roots {
+            let z = o.z() + root * d.z();
+            if Interval::new(ray_t.min, closest).surrounds(root) && (0. ..=height).contains(&z) {
+                let p = o + d * root;
+                // Gradient of x^2 + y^2 - k^2 (height - z)^2
+                let n = Vec3::new(p.x(), p.y(), k2 * (height - z));
+                closest = root;
+                found = Some((
+                    if n.near_zero() {
+                        Vec3::new(0., 0., 1.)
+                    } else {
+                        unit_vector(&n)
+                    },
+                    azimuth_uv(&p),
+                    z / height,
+                ));
+                break;
+            }
+        }
+
+        // Base cap
+        if f64::abs(d.z()) > 1e-12 {
+            let root = -o.z() / d.z();
+            let p = o + d * root;
+            if Interval::new(ray_t.min, closest).surrounds(root)
+                && p.x() * p.x() + p.y() * p.y() <= radius * radius
+            {
+                closest = root;
+                found = Some((
+                    Vec3::new(0., 0., -1.),
+                    0.5 + p.x() / (2. * radius),
+                    0.5 + p.y() / (2. * radius),
+                ));
+            }
+        }
+
+        let Some((local_normal, u, v)) = found else {
+            return false;
+        };
+        rec.t = closest;
+        rec.p = r.at(closest);
+        rec.u = u;
+        rec.v = v;
+        rec.material = self.material.clone();
+        rec.set_face_normal(r, &self.frame.transform(&local_normal));
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{grey, hit};
+
+    // Base of radius 1 on the origin, apex at y = 2
+    fn upright_cone() -> Cone {
+        Cone::new(Point3::new(0., 0., 0.), Point3::new(0., 2., 0.), 1., grey())
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn hit_side_and_base() {
+        let cone = upright_cone();
+        // Halfway up the radius is 0.5
+        let side = Ray::new(Point3::new(5., 1., 0.), Vec3::new(-1., 0., 0.));
+        let rec = hit(&cone, &side).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert_close(rec.normal, unit_vector(&Vec3::new(2., 1., 0.)));
+        assert!((rec.v - 0.5).abs() < 1e-9);
+
+        let base = Ray::new(Point3::new(0., -5., 0.), Vec3::new(0., 1., 0.));
+        let rec = hit(&cone, &base).unwrap();
+        assert!((rec.t - 5.).abs() < 1e-9);
+        assert_close(rec.normal, Vec3::new(0., -1., 0.));
+        assert!((rec.u - 0.5).abs() < 1e-9 && (rec.v - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn miss_above_the_apex_and_beside_the_slope() {
+        let cone = upright_cone();
+        let above = Ray::new(Point3::new(5., 2.5, 0.), Vec3::new(-1., 0., 0.));
+        assert!(hit(&cone, &above).is_none());
+        // Inside the bounding box, but outside the narrowing side
+        let beside = Ray::new(Point3::new(5., 1.5, 0.5), Vec3::new(-1., 0., 0.));
+        assert!(hit(&cone, &beside).is_none());
+    }
+
+    #[test]
+    fn tight_bounding_box() {
+        let bbox = upright_cone().bounding_box();
+        for (axis, min, max) in [(0, -1., 1.), (1, 0., 2.), (2, -1., 1.)] {
+            // Flat sides are padded a little
+            assert!((bbox.axis_interval(axis).min - min).abs() < 1e-3);
+            assert!((bbox.axis_interval(axis).max - max).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn degenerate_cones_are_never_hit() {
+        let p = Point3::new(0., 0., 0.);
+        let flat = Cone::new(p, p, 1., grey());
+        let thin = Cone::new(p, Point3::new(0., 2., 0.), 0., grey());
+        let r = Ray::new(Point3::new(0., -5., 0.), Vec3::new(0., 1., 0.));
+        for cone in [flat, thin] {
+            assert!(hit(&cone, &r).is_none());
+            assert_eq!(cone.bounding_box(), AABB::empty());
+        }
+    }
+}
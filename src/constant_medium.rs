@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::{Isotropic, Material},
+    ray::Ray,
+    texture::Texture,
+    utils::random_double,
+    vec3::Vec3,
+};
+
+/// A volume of constant density filling a closed boundary, e.g. smoke or fog. A ray passing
+/// through it scatters at a random distance that depends on the density.
+#[derive(Debug)]
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Option<Arc<dyn Material>>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase_function: Some(Arc::new(Isotropic::with_texture(tex))),
+        }
+    }
+
+    pub fn with_color(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase_function: Some(Arc::new(Isotropic::new(albedo))),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Find where the ray enters and leaves the boundary along the whole line, so that rays
+        // starting inside the volume are handled too. This assumes the boundary is convex.
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        if !self.boundary.hit(r, Interval::universe(), &mut rec1) {
+            return false;
+        }
+        if !self
+            .boundary
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY), &mut rec2)
+        {
+            return false;
+        }
+
+        rec1.t = f64::max(rec1.t, ray_t.min);
+        rec2.t = f64::min(rec2.t, ray_t.max);
+        if rec1.t >= rec2.t {
+            return false;
+        }
+        rec1.t = f64::max(rec1.t, 0.);
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        let hit_distance = self.neg_inv_density * f64::ln(random_double());
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = rec1.t + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+
+        // Normal and face are meaningless inside a volume, set them to arbitrary values
+        rec.normal = Vec3::new(1., 0., 0.);
+        rec.front_face = true;
+        rec.object_id = 0;
+        rec.material = self.phase_function.clone();
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundary.bounding_box()
+    }
+}
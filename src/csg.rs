@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    ray::Ray,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference, // left minus right
+}
+
+impl CsgOp {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOp::Union => in_left || in_right,
+            CsgOp::Intersection => in_left && in_right,
+            CsgOp::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Constructive solid geometry node combining two closed Hittables. Both operands are
+/// intersected along the whole ray, and the boundaries of the combined solid are found by
+/// walking through the sorted entry and exit points.
+#[derive(Debug)]
+pub struct Csg {
+    op: CsgOp,
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: AABB,
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        let (a, b) = (left.bounding_box(), right.bounding_box());
+        let bbox = match op {
+            CsgOp::Union => AABB::with_boxes(&a, &b),
+            CsgOp::Intersection => {
+                let overlap = |axis| {
+                    let (ia, ib) = (a.axis_interval(axis), b.axis_interval(axis));
+                    Interval::new(f64::max(ia.min, ib.min), f64::min(ia.max, ib.max))
+                };
+                AABB::new(overlap(0), overlap(1), overlap(2))
+            }
+            // Removing material never grows the left operand
+            CsgOp::Difference => a,
+        };
+        Self {
+            op,
+            left,
+            right,
+            bbox,
+        }
+    }
+
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Union, left, right)
+    }
+
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Intersection, left, right)
+    }
+
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        Self::new(CsgOp::Difference, left, right)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        match self.hit_all(r, ray_t).into_iter().next() {
+            Some(first) => {
+                *rec = first;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        if !self.bbox.hit(r, ray_t) {
+            return Vec::new();
+        }
+
+        // The operands have to be intersected along the entire line, otherwise we cannot know
+        // whether the ray starts inside them
+        let left_hits = self.left.hit_all(r, Interval::universe());
+        let right_hits = self.right.hit_all(r, Interval::universe());
+
+        let mut events: Vec<(HitRecord, bool)> = left_hits
+            .into_iter()
+            .map(|rec| (rec, true))
+            .chain(right_hits.into_iter().map(|rec| (rec, false)))
+            .collect();
+        events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut hits = Vec::new();
+        for (mut rec, from_left) in events {
+            let was_inside = self.op.inside(in_left, in_right);
+            // A front face hit means the ray enters that operand
+            if from_left {
+                in_left = rec.front_face;
+            } else {
+                in_right = rec.front_face;
+            }
+            let is_inside = self.op.inside(in_left, in_right);
+            if was_inside == is_inside || !ray_t.surrounds(rec.t) {
+                continue;
+            }
+            // The stored normal already faces the ray. Only the face changes, e.g. the inside of
+            // a subtracted object becomes an outside surface of the result.
+            rec.front_face = is_inside;
+            hits.push(rec);
+        }
+        hits
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian, ray::Point3, sphere::Sphere, vec3::Vec3};
+
+    fn sphere(x: f64, radius: f64) -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point3::new(x, 0., 0.),
+            radius,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    fn ts(hits: &[HitRecord]) -> Vec<f64> {
+        hits.iter().map(|h| (h.t * 1e6).round() / 1e6).collect()
+    }
+
+    #[test]
+    fn lens_is_intersection_of_two_spheres() {
+        let lens = Csg::intersection(sphere(-0.5, 1.), sphere(0.5, 1.));
+        let r = Ray::new(Point3::new(-5., 0., 0.), Vec3::new(1., 0., 0.));
+        let hits = lens.hit_all(&r, Interval::new(0.001, f64::INFINITY));
+        assert_eq!(ts(&hits), vec![4.5, 5.5]);
+        assert!(hits[0].front_face && !hits[1].front_face);
+    }
+
+    #[test]
+    fn difference_exposes_inner_surface() {
+        let hollow = Csg::difference(sphere(0., 2.), sphere(0., 1.));
+        let r = Ray::new(Point3::new(-5., 0., 0.), Vec3::new(1., 0., 0.));
+        let hits = hollow.hit_all(&r, Interval::new(0.001, f64::INFINITY));
+        assert_eq!(ts(&hits), vec![3., 4., 6., 7.]);
+        // Hitting the carved out sphere from its inside is an exit from the solid
+        assert!(!hits[1].front_face);
+        assert!(hits[2].front_face);
+
+        // A ray starting inside the hollow first hits the inner surface
+        let inner = Ray::new(Point3::new(0., 0., 0.), Vec3::new(1., 0., 0.));
+        let mut rec = HitRecord::default();
+        assert!(hollow.hit(&inner, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 1.).abs() < 1e-9);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(-1., 0., 0.));
+    }
+}
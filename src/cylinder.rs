@@ -0,0 +1,196 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    disk::disk_bounding_box,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    onb::ONB,
+    ray::{Point3, Ray},
+    vec3::Vec3,
+};
+
+/// Closed cylinder between two end points, capped with a disk at each end
+#[derive(Debug)]
+pub struct Cylinder {
+    base: Point3,
+    height: f64,
+    radius: f64,
+    frame: ONB, // w points from the base towards the top
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl Cylinder {
+    /// A cylinder with both ends at the same point or no radius has nothing to hit and an empty
+    /// box
+    pub fn new(base: Point3, top: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let axis = top - base;
+        let radius = f64::max(0., radius);
+        if axis.near_zero() || radius == 0. {
+            return Self {
+                base,
+                height: 0.,
+                radius: 0.,
+                frame: ONB::new(&Vec3::new(0., 0., 1.)),
+                material: Some(material),
+                bbox: AABB::empty(),
+            };
+        }
+        Self {
+            base,
+            height: axis.length(),
+            radius,
+            frame: ONB::new(&axis),
+            material: Some(material),
+            bbox: AABB::with_boxes(
+                &disk_bounding_box(&base, &axis, radius),
+                &disk_bounding_box(&top, &axis, radius),
+            ),
+        }
+    }
+}
+
+/// Angle around the local z axis mapped to [0,1]
+pub(crate) fn azimuth_uv(local: &Vec3) -> f64 {
+    (f64::atan2(local.y(), local.x()) + PI) / (2. * PI)
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if self.radius <= 0. {
+            return false;
+        }
+        // Intersect in the cylinder's local frame, where it stands on the origin along +z. The
+        // frame is orthonormal so t is the same in both spaces.
+        let o = self.frame.to_local(&(r.origin() - self.base));
+        let d = self.frame.to_local(&r.direction());
+        let (radius, height) = (self.radius, self.height);
+
+        let mut closest = ray_t.max;
+        let mut found: Option<(Vec3, f64, f64)> = None; // local normal, u, v
+
+        // Side: x^2 + y^2 = radius^2 with 0 <= z <= height
+        let a = d.x() * d.x() + d.y() * d.y();
+        if a > 1e-12 {
+            let h = o.x() * d.x() + o.y() * d.y();
+            let c = o.x() * o.x() + o.y() * o.y() - radius * radius;
+            let discriminant = h * h - a * c;
+            if discriminant >= 0. {
+                let sqrtd = f64::sqrt(discriminant);
+                for root in [(-h - sqrtd) / a, (-h + sqrtd) / a] {
+                    let z = o.z() + root * d.z();
+                    if Interval::new(ray_t.min, closest).surrounds(root)
+                        && (0. ..=height).contains(&z)
+                    {
+                        let p = o + d * root;
+                        closest = root;
+                        found = Some((
+                            Vec3::new(p.x() / radius, p.y() / radius, 0.),
+                            azimuth_uv(&p),
+                            z / height,
+                        ));
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Caps: planes z = 0 and z = height, inside the radius
+        if f64::abs(d.z()) > 1e-12 {
+            for (z, nz) in [(0., -1.), (height, 1.)] {
+                let root = (z - o.z()) / d.z();
+                if !Interval::new(ray_t.min, closest).surrounds(root) {
+                    continue;
+                }
+                let p = o + d * root;
+                if p.x() * p.x() + p.y() * p.y() <= radius * radius {
+                    closest = root;
+                    found = Some((
+                        Vec3::new(0., 0., nz),
+                        0.5 + p.x() / (2. * radius),
+                        0.5 + p.y() / (2. * radius),
+                    ));
+                }
+            }
+        }
+
+        let Some((local_normal, u, v)) = found else {
+            return false;
+        };
+        rec.t = closest;
+        rec.p = r.at(closest);
+        rec.u = u;
+        rec.v = v;
+        rec.material = self.material.clone();
+        rec.set_face_normal(r, &self.frame.transform(&local_normal));
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{grey, hit};
+
+    // Radius 1 from the origin up to z = 2
+    fn upright_cylinder() -> Cylinder {
+        Cylinder::new(Point3::new(0., 0., 0.), Point3::new(0., 0., 2.), 1., grey())
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn hit_side_and_caps() {
+        let cylinder = upright_cylinder();
+        let side = Ray::new(Point3::new(5., 0., 1.), Vec3::new(-1., 0., 0.));
+        let rec = hit(&cylinder, &side).unwrap();
+        assert!((rec.t - 4.).abs() < 1e-9);
+        assert_close(rec.normal, Vec3::new(1., 0., 0.));
+        assert!((rec.v - 0.5).abs() < 1e-9);
+
+        let top = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let rec = hit(&cylinder, &top).unwrap();
+        assert!((rec.t - 3.).abs() < 1e-9);
+        assert_close(rec.normal, Vec3::new(0., 0., 1.));
+        assert!((rec.u - 0.5).abs() < 1e-9 && (rec.v - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn miss_beside_and_past_the_ends() {
+        let cylinder = upright_cylinder();
+        let beside = Ray::new(Point3::new(5., 1.5, 1.), Vec3::new(-1., 0., 0.));
+        assert!(hit(&cylinder, &beside).is_none());
+        let above = Ray::new(Point3::new(5., 0., 2.5), Vec3::new(-1., 0., 0.));
+        assert!(hit(&cylinder, &above).is_none());
+    }
+
+    #[test]
+    fn tight_bounding_box() {
+        let bbox = upright_cylinder().bounding_box();
+        for (axis, min, max) in [(0, -1., 1.), (1, -1., 1.), (2, 0., 2.)] {
+            // Flat sides are padded a little
+            assert!((bbox.axis_interval(axis).min - min).abs() < 1e-3);
+            assert!((bbox.axis_interval(axis).max - max).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn degenerate_cylinders_are_never_hit() {
+        let p = Point3::new(0., 0., 0.);
+        let flat = Cylinder::new(p, p, 1., grey());
+        let thin = Cylinder::new(p, Point3::new(0., 0., 2.), 0., grey());
+        let r = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        for cylinder in [flat, thin] {
+            assert!(hit(&cylinder, &r).is_none());
+            assert_eq!(cylinder.bounding_box(), AABB::empty());
+        }
+    }
+}
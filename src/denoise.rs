@@ -0,0 +1,143 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{aov::Aovs, color::Color, vec3::Vec3};
+
+/// Edge-avoiding à-trous wavelet filter (Dammertz et al., 2010). Every iteration blurs with a
+/// 5x5 B3 spline kernel whose taps are spread twice as far apart as in the previous one, and
+/// each tap is weighted down where the color, normal, albedo or depth differ from the centre
+/// pixel, so edges and texture details survive while noise is smoothed away.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DenoiseSettings {
+    pub iterations: u32,
+    pub color_sigma: f64,  // in gamma corrected color, halved every iteration
+    pub normal_sigma: f64, // distance between unit normals
+    pub albedo_sigma: f64,
+    pub depth_sigma: f64, // relative to the depth of the centre pixel
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 5,
+            color_sigma: 0.6,
+            normal_sigma: 0.3,
+            albedo_sigma: 0.1,
+            depth_sigma: 0.05,
+        }
+    }
+}
+
+const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+/// Filters a linear `color` image using the feature buffers in `aovs`, which must have the same
+/// size. Best used with AOVs rendered with several samples per pixel, so the features are
+/// antialiased like the colors.
+pub fn denoise(color: &[Color], aovs: &Aovs, settings: &DenoiseSettings) -> Vec<Color> {
+    let (width, height) = (aovs.width, aovs.height);
+    let mut current = color.to_vec();
+    for iteration in 0..settings.iterations {
+        let step = 1 << iteration;
+        let color_sigma = settings.color_sigma / (1 << iteration) as f64;
+        let previous = current;
+        current = (0..height)
+            .into_par_iter()
+            .flat_map(|y| {
+                let row: Vec<Color> = (0..width)
+                    .map(|x| filter_pixel(&previous, aovs, settings, color_sigma, x, y, step))
+                    .collect();
+                row
+            })
+            .collect();
+    }
+    current
+}
+
+fn filter_pixel(
+    color: &[Color],
+    aovs: &Aovs,
+    settings: &DenoiseSettings,
+    color_sigma: f64,
+    x: usize,
+    y: usize,
+    step: usize,
+) -> Color {
+    let p = y * aovs.width + x;
+    let color_p = gamma(color[p]);
+    let mut sum = Color::default();
+    let mut weight_sum = 0.;
+    for (ky, kernel_y) in KERNEL.iter().enumerate() {
+        let qy = y as isize + (ky as isize - 2) * step as isize;
+        if qy < 0 || qy >= aovs.height as isize {
+            continue;
+        }
+        for (kx, kernel_x) in KERNEL.iter().enumerate() {
+            let qx = x as isize + (kx as isize - 2) * step as isize;
+            if qx < 0 || qx >= aovs.width as isize {
+                continue;
+            }
+            let q = qy as usize * aovs.width + qx as usize;
+
+            let depth_scale = f64::max(f64::max(aovs.depth[p], aovs.depth[q]), 1e-3);
+            let exponent = (gamma(color[q]) - color_p).length_squared()
+                / (color_sigma * color_sigma)
+                + (aovs.normal[q] - aovs.normal[p]).length_squared()
+                    / (settings.normal_sigma * settings.normal_sigma)
+                + (aovs.albedo[q] - aovs.albedo[p]).length_squared()
+                    / (settings.albedo_sigma * settings.albedo_sigma)
+                + ((aovs.depth[q] - aovs.depth[p]) / (depth_scale * settings.depth_sigma)).powi(2);
+            let weight = kernel_x * kernel_y * f64::exp(-exponent);
+            sum += color[q] * weight;
+            weight_sum += weight;
+        }
+    }
+    // The centre tap always has a weight of at least KERNEL[2]^2
+    sum / weight_sum
+}
+
+// Compares colors the way they are displayed, see Color::get_rgb
+fn gamma(c: Color) -> Vec3 {
+    Vec3::new(
+        f64::sqrt(f64::max(c.x(), 0.)),
+        f64::sqrt(f64::max(c.y(), 0.)),
+        f64::sqrt(f64::max(c.z(), 0.)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{random_double, seed_random};
+
+    #[test]
+    fn smooths_noise_but_keeps_albedo_edges() {
+        // Left half dark, right half bright, both with noise
+        let (width, height) = (16, 16);
+        let mut aovs = Aovs::new(width, height);
+        seed_random(1);
+        let mut color = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let base = if x < width / 2 { 0.1 } else { 0.8 };
+                aovs.albedo[y * width + x] = Color::new(base, base, base);
+                aovs.normal[y * width + x] = Vec3::new(0., 0., 1.);
+                aovs.depth[y * width + x] = 1.;
+                let noisy = base * (0.5 + random_double());
+                color.push(Color::new(noisy, noisy, noisy));
+            }
+        }
+        let filtered = denoise(&color, &aovs, &DenoiseSettings::default());
+
+        let variance = |image: &[Color], x_range: std::ops::Range<usize>| {
+            let values: Vec<f64> = (0..height)
+                .flat_map(|y| x_range.clone().map(move |x| y * width + x))
+                .map(|i| image[i].x())
+                .collect();
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64
+        };
+        assert!(variance(&filtered, 8..16) < 0.25 * variance(&color, 8..16));
+        // Nothing of the bright half bleeds into the dark one
+        assert!(filtered[..width / 2].iter().all(|c| c.x() < 0.2));
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    onb::ONB,
+    ray::{Point3, Ray},
+    vec3::{dot, unit_vector, Vec3},
+};
+
+#[derive(Debug)]
+pub struct Disk {
+    center: Point3,
+    radius: f64,
+    frame: ONB, // w is the disk normal
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl Disk {
+    /// A disk without a normal or a radius has nothing to hit and an empty box
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Arc<dyn Material>) -> Self {
+        let radius = f64::max(0., radius);
+        if normal.near_zero() || radius == 0. {
+            return Self {
+                center,
+                radius: 0.,
+                frame: ONB::new(&Vec3::new(0., 0., 1.)),
+                material: Some(material),
+                bbox: AABB::empty(),
+            };
+        }
+        Self {
+            center,
+            radius,
+            frame: ONB::new(&normal),
+            material: Some(material),
+            bbox: disk_bounding_box(&center, &normal, radius),
+        }
+    }
+}
+
+/// Tight box around a disk. Along each axis the disk extends by the radius scaled by the sine of
+/// the angle between that axis and the disk normal.
+pub fn disk_bounding_box(center: &Point3, normal: &Vec3, radius: f64) -> AABB {
+    let n = unit_vector(normal);
+    let extent = Vec3::new(
+        radius * f64::sqrt(f64::max(0., 1. - n.x() * n.x())),
+        radius * f64::sqrt(f64::max(0., 1. - n.y() * n.y())),
+        radius * f64::sqrt(f64::max(0., 1. - n.z() * n.z())),
+    );
+    AABB::with_points(&(*center - extent), &(*center + extent))
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if self.radius <= 0. {
+            return false;
+        }
+        let normal = self.frame.w();
+        let denom = dot(normal, r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if f64::abs(denom) < 1e-8 {
+            return false;
+        }
+
+        let t = dot(normal, self.center - r.origin()) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        let p = r.at(t);
+        let local = self.frame.to_local(&(p - self.center));
+        if local.x() * local.x() + local.y() * local.y() > self.radius * self.radius {
+            return false;
+        }
+
+        // Planar mapping of the disk onto the unit square
+        rec.u = 0.5 + local.x() / (2. * self.radius);
+        rec.v = 0.5 + local.y() / (2. * self.radius);
+        rec.t = t;
+        rec.p = p;
+        rec.material = self.material.clone();
+        rec.set_face_normal(r, &normal);
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{grey, hit};
+
+    fn disk(normal: Vec3, radius: f64) -> Disk {
+        Disk::new(Point3::new(0., 0., 0.), normal, radius, grey())
+    }
+
+    #[test]
+    fn hit_inside_sets_uv() {
+        let disk = disk(Vec3::new(0., 0., 1.), 1.);
+        let r = Ray::new(Point3::new(0.5, 0., 1.), Vec3::new(0., 0., -1.));
+        let rec = hit(&disk, &r).unwrap();
+        assert_eq!(rec.t, 1.);
+        assert!(rec.front_face);
+        // Half the radius from the centre, which maps to 0.5 in uv
+        let (du, dv) = (rec.u - 0.5, rec.v - 0.5);
+        assert!((du * du + dv * dv - 0.0625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn miss_outside_and_parallel() {
+        let disk = disk(Vec3::new(0., 0., 1.), 1.);
+        let outside = Ray::new(Point3::new(0.8, 0.8, 1.), Vec3::new(0., 0., -1.));
+        assert!(hit(&disk, &outside).is_none());
+        let parallel = Ray::new(Point3::new(0., 0., 1.), Vec3::new(1., 0., 0.));
+        assert!(hit(&disk, &parallel).is_none());
+    }
+
+    #[test]
+    fn tight_bounding_box() {
+        // Tilted 45 degrees around z, so it reaches sin(45) along x and y and 1 along z
+        let bbox = disk(Vec3::new(1., 1., 0.), 1.).bounding_box();
+        let half = f64::sqrt(0.5);
+        for (axis, extent) in [(0, half), (1, half), (2, 1.)] {
+            assert!((bbox.axis_interval(axis).max - extent).abs() < 1e-9);
+            assert!((bbox.axis_interval(axis).min + extent).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn degenerate_disks_are_never_hit() {
+        let r = Ray::new(Point3::new(0., 0., 1.), Vec3::new(0., 0., -1.));
+        for disk in [disk(Vec3::default(), 1.), disk(Vec3::new(0., 0., 1.), 0.)] {
+            assert!(hit(&disk, &r).is_none());
+            assert_eq!(disk.bounding_box(), AABB::empty());
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    mesh::Mesh,
+    perlin::Perlin,
+    ray::{Point3, Ray},
+    texture::RTImage,
+    triangle::Triangle,
+    vec3::{unit_vector, Vec3},
+};
+
+/// Terrain built from a regular grid of heights. Every grid cell is split into two smoothly
+/// shaded triangles, which are put in a BVH so only the cells near the ray are tested.
+#[derive(Debug)]
+pub struct Heightfield {
+    mesh: Mesh,
+    nx: usize,
+    nz: usize,
+}
+
+impl Heightfield {
+    /// `heights` holds `nx * nz` samples in row-major order (x changes fastest). The grid spans
+    /// `size.x()` by `size.z()` starting at `corner`, and each height is scaled by `size.y()`.
+    pub fn new(
+        heights: &[f64],
+        nx: usize,
+        nz: usize,
+        corner: Point3,
+        size: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        if nx < 2 || nz < 2 || heights.len() < nx * nz {
+            eprintln!(
+                "ERROR: Heightfield needs at least a 2x2 grid of heights, got {}x{}",
+                nx, nz
+            );
+            return Self {
+                mesh: Mesh::from_triangles(Vec::new()),
+                nx,
+                nz,
+            };
+        }
+
+        let dx = size.x() / (nx - 1) as f64;
+        let dz = size.z() / (nz - 1) as f64;
+        let height = |i: usize, j: usize| heights[j * nx + i] * size.y();
+        let position =
+            |i: usize, j: usize| corner + Vec3::new(i as f64 * dx, height(i, j), j as f64 * dz);
+        let uv = |i: usize, j: usize| (i as f64 / (nx - 1) as f64, j as f64 / (nz - 1) as f64);
+
+        // Vertex normals from the slope of the height function (central differences inside
+        // the grid, one-sided differences on its border)
+        let normal = |i: usize, j: usize| {
+            let (i0, i1) = (i.saturating_sub(1), usize::min(i + 1, nx - 1));
+            let (j0, j1) = (j.saturating_sub(1), usize::min(j + 1, nz - 1));
+            let slope_x = (height(i1, j) - height(i0, j)) / ((i1 - i0) as f64 * dx);
+            let slope_z = (height(i, j1) - height(i, j0)) / ((j1 - j0) as f64 * dz);
+            unit_vector(&Vec3::new(-slope_x, 1., -slope_z))
+        };
+
+        let mut triangles: Vec<Arc<dyn Hittable>> = Vec::with_capacity(2 * (nx - 1) * (nz - 1));
+        for j in 0..nz - 1 {
+            for i in 0..nx - 1 {
+                for corners in [
+                    [(i, j), (i, j + 1), (i + 1, j)],
+                    [(i + 1, j), (i, j + 1), (i + 1, j + 1)],
+                ] {
+                    triangles.push(Arc::new(Triangle::with_attributes(
+                        corners.map(|(a, b)| position(a, b)),
+                        Some(corners.map(|(a, b)| normal(a, b))),
+                        Some(corners.map(|(a, b)| uv(a, b))),
+                        material.clone(),
+                    )));
+                }
+            }
+        }
+
+        Self {
+            mesh: Mesh::from_triangles(triangles),
+            nx,
+            nz,
+        }
+    }
+
+    /// Samples `f(u, v)` with u, v in [0,1] on an `nx` by `nz` grid
+    pub fn from_fn(
+        nx: usize,
+        nz: usize,
+        corner: Point3,
+        size: Vec3,
+        material: Arc<dyn Material>,
+        f: impl Fn(f64, f64) -> f64,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let u = i as f64 / nx.saturating_sub(1).max(1) as f64;
+                let v = j as f64 / nz.saturating_sub(1).max(1) as f64;
+                heights.push(f(u, v));
+            }
+        }
+        Self::new(&heights, nx, nz, corner, size, material)
+    }
+
+    /// Uses the brightness of an image from the `textures` directory as height, one grid
+    /// vertex per pixel
+    pub fn from_image(
+        filename: &str,
+        corner: Point3,
+        size: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let image = RTImage::new(filename);
+        let (nx, nz) = (image.width() as usize, image.height() as usize);
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let [r, g, b] = image.get_linear_pixel(i as u32, j as u32);
+                heights.push(0.2126 * r + 0.7152 * g + 0.0722 * b);
+            }
+        }
+        Self::new(&heights, nx, nz, corner, size, material)
+    }
+
+    /// Heights from Perlin noise, `scale` controls how many noise features fit in the grid
+    pub fn from_noise(
+        noise: &Perlin,
+        scale: f64,
+        nx: usize,
+        nz: usize,
+        corner: Point3,
+        size: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self::from_fn(nx, nz, corner, size, material, |u, v| {
+            noise.noise(&Point3::new(u * scale, 0., v * scale))
+        })
+    }
+
+    pub fn resolution(&self) -> (usize, usize) {
+        (self.nx, self.nz)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.mesh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.mesh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian};
+
+    #[test]
+    fn slope_normal_and_uv() {
+        // A ramp rising along x: height = u
+        let field = Heightfield::from_fn(
+            5,
+            5,
+            Point3::new(0., 0., 0.),
+            Vec3::new(4., 4., 4.),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            |u, _| u,
+        );
+        let r = Ray::new(Point3::new(1.5, 10., 2.5), Vec3::new(0., -1., 0.));
+        let mut rec = HitRecord::default();
+        assert!(field.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.p.y() - 1.5).abs() < 1e-9);
+        assert!((rec.normal - unit_vector(&Vec3::new(-1., 1., 0.))).length() < 1e-9);
+        assert!((rec.u - 0.375).abs() < 1e-9);
+        assert!((rec.v - 0.625).abs() < 1e-9);
+    }
+}
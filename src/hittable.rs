@@ -5,6 +5,7 @@ use crate::{
     interval::Interval,
     material::Material,
     ray::{Point3, Ray},
+    utils::random_int,
     vec3::{dot, Vec3},
 };
 
@@ -17,6 +18,7 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    pub object_id: u32, // set by ObjectId, 0 for objects without one
 }
 
 impl HitRecord {
@@ -32,6 +34,18 @@ impl HitRecord {
     }
 }
 
+/// Hits `object` with the object ID of `rec` cleared first, so a closer hit on an untagged object
+/// does not keep the ID of the object it replaces. On a miss `rec` keeps its ID.
+pub fn hit_replacing(object: &dyn Hittable, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    let previous_id = rec.object_id;
+    rec.object_id = 0;
+    if object.hit(r, ray_t, rec) {
+        return true;
+    }
+    rec.object_id = previous_id;
+    false
+}
+
 pub trait Hittable: Send + Sync + Debug {
     // Check if Hittable object has been hit. Hit will be implemented differently depending on the
     // struct, but it mostly mutate the HitRecord to store information.
@@ -42,9 +56,45 @@ pub trait Hittable: Send + Sync + Debug {
     // that are further than the closest object hit.
     fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
     fn bounding_box(&self) -> AABB;
+
+    // Every intersection of the ray with the object inside ray_t, sorted by t. This is what
+    // constructive solid geometry needs to know where a ray enters and leaves an object.
+    //
+    // The default implementation repeatedly asks for the closest hit past the previous one,
+    // objects that can find all their roots at once should override it.
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        let mut hits: Vec<HitRecord> = Vec::new();
+        let mut t_min = ray_t.min;
+        while hits.len() < MAX_HITS_PER_RAY {
+            let mut rec = HitRecord::default();
+            if !self.hit(r, Interval::new(t_min, ray_t.max), &mut rec) {
+                break;
+            }
+            // Step past the hit so it is not found again
+            t_min = rec.t + HIT_ALL_EPSILON * f64::max(1., f64::abs(rec.t));
+            hits.push(rec);
+        }
+        hits
+    }
+
+    // Probability density, with respect to solid angle, that `random` returns `direction` when
+    // called from `origin`. Objects that can be sampled directly (e.g. as light sources)
+    // override this together with `random`.
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.
+    }
+
+    // Random direction from `origin` towards the object
+    fn random(&self, _origin: &Point3) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
 }
 
-#[derive(Debug)]
+// Safety net for hit_all on badly behaved (e.g. self-intersecting) geometry
+const MAX_HITS_PER_RAY: usize = 64;
+const HIT_ALL_EPSILON: f64 = 1e-7;
+
+#[derive(Debug, Clone)]
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
     bbox: AABB,
@@ -78,7 +128,12 @@ impl Hittable for HittableList {
         let mut closest_so_far = ray_t.max;
 
         for obj in &self.objects {
-            if obj.hit(r, Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
+            if hit_replacing(
+                obj.as_ref(),
+                r,
+                Interval::new(ray_t.min, closest_so_far),
+                &mut temp_rec,
+            ) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
                 *rec = temp_rec.clone()
@@ -90,6 +145,74 @@ impl Hittable for HittableList {
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // Sampling a list picks one of its objects uniformly, so the density is the average of the
+    // objects' densities
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        if self.objects.is_empty() {
+            return 0.;
+        }
+        let weight = 1. / self.objects.len() as f64;
+        self.objects
+            .iter()
+            .map(|object| weight * object.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::new(1., 0., 0.);
+        }
+        let index = random_int(0, self.objects.len() as i32 - 1) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(origin)
+    }
+}
+
+/// Tags every hit on `object` with `id`, for the object ID AOV. When IDs are nested the innermost
+/// one wins.
+#[derive(Debug)]
+pub struct ObjectId {
+    id: u32,
+    object: Arc<dyn Hittable>,
+}
+
+impl ObjectId {
+    // `id` should not be 0, which stands for untagged objects
+    pub fn new(id: u32, object: Arc<dyn Hittable>) -> Self {
+        Self { id, object }
+    }
+}
+
+impl Hittable for ObjectId {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        if !hit_replacing(self.object.as_ref(), r, ray_t, rec) {
+            return false;
+        }
+        if rec.object_id == 0 {
+            rec.object_id = self.id;
+        }
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.object.bounding_box()
+    }
+
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        let mut hits = self.object.hit_all(r, ray_t);
+        for rec in hits.iter_mut().filter(|rec| rec.object_id == 0) {
+            rec.object_id = self.id;
+        }
+        hits
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.object.random(origin)
+    }
 }
 
 pub struct HittableAxisCompare(Arc<dyn Hittable>);
@@ -116,3 +239,36 @@ impl HittableAxisCompare {
         Self::box_compare(a, b, 2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bvh::BVHNode, color::Color, material::Lambertian, sphere::Sphere};
+
+    #[test]
+    fn object_id_does_not_leak_to_closer_hits() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let far = Arc::new(Sphere::new(Point3::new(0., 0., -5.), 1., material.clone()));
+        let near = Arc::new(Sphere::new(Point3::new(0., 0., -2.), 0.5, material));
+        let mut world = HittableList::new();
+        world.add(Arc::new(ObjectId::new(7, far)));
+        world.add(near);
+
+        let mut rec = HitRecord::default();
+        let r = Ray::new(Point3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+        assert!(world.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_eq!((rec.t, rec.object_id), (1.5, 0));
+
+        let r = Ray::new(Point3::new(0., 0.8, 0.), Vec3::new(0., 0., -1.));
+        assert!(world.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_eq!(rec.object_id, 7);
+
+        // A record reused from a tagged hit, through a BVH this time
+        let bvh = BVHNode::new(&mut world);
+        assert!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_eq!(rec.object_id, 7);
+        let r = Ray::new(Point3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+        assert!(bvh.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_eq!((rec.t, rec.object_id), (1.5, 0));
+    }
+}
@@ -1,14 +1,39 @@
 pub mod aabb;
+pub mod animation;
+pub mod aov;
+pub mod background;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod cone;
+pub mod constant_medium;
+pub mod csg;
+pub mod cylinder;
+pub mod denoise;
+pub mod disk;
+pub mod heightfield;
 pub mod hittable;
 pub mod interval;
 pub mod material;
+pub mod mesh;
+pub mod numeric;
+pub mod obj;
+pub mod onb;
+pub mod pdf;
 pub mod perlin;
+pub mod quad;
 pub mod ray;
+pub mod sampler;
 pub mod scene;
+pub mod sdf;
+pub mod spectrum;
 pub mod sphere;
+#[cfg(test)]
+mod testing;
 pub mod texture;
+pub mod tile;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 pub mod utils;
 pub mod vec3;
@@ -1,16 +1,33 @@
-use std::{f64::consts, fs::File, io::Write, sync::Arc};
+use std::{f64::consts, fs::File, io::Write, sync::Arc, time::Duration};
 
 use rrtm::{
+    aabb::AABB,
+    animation::{Animated, Animation, CameraPose, Interpolation, Keyframe, Track},
+    background::Background,
     bvh::BVHNode,
-    camera::Camera,
+    camera::{
+        sample_count_heatmap, AdaptiveSampling, Camera, CameraBuilder, FisheyeMapping, Projection,
+    },
     color::Color,
-    hittable::Hittable,
-    hittable::HittableList,
+    cone::Cone,
+    constant_medium::ConstantMedium,
+    csg::Csg,
+    cylinder::Cylinder,
+    denoise::{self, DenoiseSettings},
+    disk::Disk,
+    heightfield::Heightfield,
+    hittable::{Hittable, HittableList, ObjectId},
     material::*,
+    mesh::Mesh,
+    quad::{make_box, Quad},
     ray::Point3,
+    sdf::{self, Sdf},
     sphere::Sphere,
     texture::{CheckerTexture, ImageTexture, NoiseTexture},
-    utils::{random_double, random_double_range},
+    tile::RenderControl,
+    torus::Torus,
+    transform::{Affine, Transform},
+    utils::{random_double, random_double_range, seed_random, DEFAULT_SEED},
     vec3::Vec3,
 };
 
@@ -19,25 +36,145 @@ fn main() {
     let now = Instant::now();
     let out = std::io::stdout();
 
-    let (camera, world) = perlin();
-    let pixels = camera.render(&world);
+    // Optional seed after the scene name, the same seed always gives the same image
+    let seed = std::env::args()
+        .nth(2)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_SEED);
+    seed_random(seed);
+
+    let (mut camera, world, lights) = match std::env::args().nth(1).as_deref() {
+        Some("quads") => quads(),
+        Some("simple_light") => simple_light(),
+        Some("smoke") => smoke(),
+        Some("cornell_box") => cornell_box(),
+        Some("shapes") => shapes(),
+        Some("adaptive") => adaptive(),
+        Some("isometric") => isometric(),
+        Some("panorama") => panorama(),
+        Some("fisheye") => fisheye(),
+        Some("sdf") => sdf_shapes(),
+        Some("csg") => csg(),
+        Some("terrain") => terrain(),
+        Some("torus") => tori(),
+        Some("dispersion") => dispersion(),
+        Some("pyramid") => pyramid(),
+        Some("keyframes") => keyframes(),
+        Some("mike") => mike(),
+        Some("earth") => earth(),
+        Some("checkered_sphere") => checkered_sphere(),
+        Some("much_sphere") => render_much_sphere(),
+        _ => perlin(),
+    };
+    camera.seed = seed;
+    // RRTM_ANIMATION=directory renders a turntable of the scene into numbered frames instead,
+    // skipping the frames already in the directory
+    if let Ok(directory) = std::env::var("RRTM_ANIMATION") {
+        let directory = std::path::PathBuf::from(directory);
+        let animation = turntable(&camera, world, lights);
+        animation
+            .render(&camera, &directory, 1, |frame| {
+                eprintln!("rendered frame {}/{}", frame, animation.frame_count);
+            })
+            .expect("could not render the animation");
+        eprintln!(
+            "rendered {} frames in {:?}",
+            animation.frame_count,
+            now.elapsed()
+        );
+        return;
+    }
+    // RRTM_TIME_LIMIT=seconds stops starting new tiles after that long, the rest stay black
+    let control = match std::env::var("RRTM_TIME_LIMIT")
+        .ok()
+        .and_then(|arg| arg.parse().ok())
+    {
+        Some(seconds) => RenderControl::with_time_budget(Duration::from_secs_f64(seconds)),
+        None => RenderControl::new(),
+    };
+    let tiles = camera.tiles();
+    let pixel_count = camera.image_width() * camera.image_height();
+    let mut pixels = vec![Color::default(); pixel_count];
+    let mut sample_counts = vec![0; pixel_count];
+    let mut finished = 0;
+    let status = camera.render_tiles(&world, &lights, &tiles, 0, &control, |result| {
+        let tile = result.tile;
+        tile.copy_into(&result.pixels, &mut pixels, camera.image_width);
+        tile.copy_into(
+            &result.sample_counts,
+            &mut sample_counts,
+            camera.image_width,
+        );
+        finished += 1;
+        eprint!("\rrendered {}/{} tiles", finished, tiles.len());
+    });
+    eprintln!(" ({:?})", status);
+
+    // RRTM_HEATMAP=path also writes how many samples every pixel took, to tune adaptive sampling
+    if let Ok(path) = std::env::var("RRTM_HEATMAP") {
+        let file = File::create(path).expect("could not create the heatmap file");
+        write_ppm(&file, &camera, &sample_count_heatmap(&sample_counts));
+    }
+    // RRTM_AOVS=prefix writes depth, normal, albedo, uv and object ID images next to the render,
+    // RRTM_DENOISE=1 filters the render guided by those same AOVs
+    let aov_prefix = std::env::var("RRTM_AOVS").ok();
+    let denoise = std::env::var("RRTM_DENOISE").is_ok_and(|arg| arg != "0");
+    if aov_prefix.is_some() || denoise {
+        let aovs = camera.render_aovs(&world);
+        if let Some(prefix) = aov_prefix {
+            aovs.save(&prefix).expect("could not write the AOV images");
+        }
+        if denoise {
+            pixels = denoise::denoise(&pixels, &aovs, &DenoiseSettings::default());
+        }
+    }
+    write_ppm(&out, &camera, &pixels);
+    let elapsed = now.elapsed();
+    dbg!(elapsed);
+}
+
+// Two seconds of the camera circling around what it looks at
+fn turntable(camera: &Camera, world: Arc<dyn Hittable>, lights: HittableList) -> Animation {
+    let pose = CameraPose::of(camera);
+    let (offset, up) = (pose.lookfrom - pose.lookat, camera.vup);
+    let duration = 2.;
+    Animation::new(
+        48,
+        24.,
+        move |time| {
+            let turn = Affine::rotation(up, 360. * time / duration);
+            CameraPose {
+                lookfrom: pose.lookat + turn.transform_vector(&offset),
+                ..pose
+            }
+        },
+        move |_, _| (world.clone(), lights.clone()),
+    )
+}
+
+fn write_ppm(mut out: impl Write, camera: &Camera, pixels: &[Color]) {
     let _ = writeln!(
-        &out,
+        out,
         "P3\n{} {}\n255\n",
         camera.image_width, camera.image_height
     );
     for p in pixels {
-        let _ = writeln!(&out, "{}", p.get_string());
+        let _ = writeln!(out, "{}", p.get_string());
     }
-    let elapsed = now.elapsed();
-    dbg!(elapsed);
 }
 
-pub fn perlin() -> (Camera, Arc<dyn Hittable>) {
+pub fn perlin() -> (Camera, Arc<dyn Hittable>, HittableList) {
     let lookfrom = Point3::new(13., 2., 3.);
     let lookat = Point3::new(0., 0., 0.);
     let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(400, 16. / 9., 100, 50, 20., lookfrom, lookat, vup, 0., 12.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .focus_dist(12.)
+        .build()
+        .expect("invalid camera settings");
 
     let mut world = HittableList::new();
     let pertext = Arc::new(NoiseTexture::new());
@@ -53,13 +190,714 @@ pub fn perlin() -> (Camera, Arc<dyn Hittable>) {
     ));
     world.add(ground);
     world.add(sphere);
-    (camera, BVHNode::new(&mut world) as Arc<dyn Hittable>)
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn quads() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 0., 9.);
+    let lookat = Point3::new(0., 0., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .aspect_ratio(1.)
+        .vfov(80.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+
+    // Materials
+    let left_red = Arc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
+    let back_green = Arc::new(Lambertian::new(Color::new(0.2, 1.0, 0.2)));
+    let right_blue = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
+    let upper_orange = Arc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
+    let lower_teal = Arc::new(Lambertian::new(Color::new(0.2, 0.8, 0.8)));
+
+    // Quads
+    world.add(Arc::new(Quad::new(
+        Point3::new(-3., -2., 5.),
+        Vec3::new(0., 0., -4.),
+        Vec3::new(0., 4., 0.),
+        left_red,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-2., -2., 0.),
+        Vec3::new(4., 0., 0.),
+        Vec3::new(0., 4., 0.),
+        back_green,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(3., -2., 1.),
+        Vec3::new(0., 0., 4.),
+        Vec3::new(0., 4., 0.),
+        right_blue,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-2., 3., 1.),
+        Vec3::new(4., 0., 0.),
+        Vec3::new(0., 0., 4.),
+        upper_orange,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-2., -3., 5.),
+        Vec3::new(4., 0., 0.),
+        Vec3::new(0., 0., -4.),
+        lower_teal,
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn simple_light() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(26., 3., 6.);
+    let lookat = Point3::new(0., 2., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .background(Background::None)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let pertext = Arc::new(NoiseTexture::new());
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(pertext.clone())),
+    )));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., 2., 0.),
+        2.,
+        Arc::new(Lambertian::with_texture(pertext)),
+    )));
+
+    let mut lights = HittableList::new();
+    let difflight = Arc::new(DiffuseLight::new(Color::new(4., 4., 4.)));
+    lights.add(Arc::new(Sphere::new(
+        Point3::new(0., 7., 0.),
+        2.,
+        difflight.clone(),
+    )));
+    lights.add(Arc::new(Quad::new(
+        Point3::new(3., 1., -2.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        difflight,
+    )));
+    for light in &lights.objects {
+        world.add(light.clone());
+    }
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        lights,
+    )
+}
+fn smoke() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(13., 2., 3.);
+    let lookat = Point3::new(0., 1., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A dark, dense blob next to a thin white fog
+    let blob = Arc::new(Sphere::new(
+        Point3::new(0., 1., -1.5),
+        1.,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    world.add(Arc::new(ConstantMedium::with_color(
+        blob,
+        2.,
+        Color::new(0.1, 0.1, 0.1),
+    )));
+    let fog = Arc::new(Sphere::new(
+        Point3::new(0., 1., 1.5),
+        1.,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    world.add(Arc::new(ConstantMedium::with_color(
+        fog,
+        0.5,
+        Color::new(1., 1., 1.),
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn cornell_box() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(278., 278., -800.);
+    let lookat = Point3::new(278., 278., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .image_width(600)
+        .aspect_ratio(1.)
+        .samples_per_pixel(200)
+        .vfov(40.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .background(Background::None)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Color::new(15., 15., 15.)));
+
+    world.add(Arc::new(Quad::new(
+        Point3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        green,
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(0., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        Vec3::new(0., 0., 555.),
+        red,
+    )));
+    let mut lights = HittableList::new();
+    lights.add(Arc::new(Quad::new(
+        Point3::new(343., 554., 332.),
+        Vec3::new(-130., 0., 0.),
+        Vec3::new(0., 0., -105.),
+        light,
+    )));
+    world.add(lights.objects[0].clone());
+    world.add(Arc::new(Quad::new(
+        Point3::new(0., 0., 0.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 0., 555.),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(555., 555., 555.),
+        Vec3::new(-555., 0., 0.),
+        Vec3::new(0., 0., -555.),
+        white.clone(),
+    )));
+    world.add(Arc::new(Quad::new(
+        Point3::new(0., 0., 555.),
+        Vec3::new(555., 0., 0.),
+        Vec3::new(0., 555., 0.),
+        white.clone(),
+    )));
+
+    let box1 = make_box(
+        &Point3::new(0., 0., 0.),
+        &Point3::new(165., 330., 165.),
+        white.clone(),
+    );
+    let box1 = Arc::new(Transform::rotate_y(box1, 15.));
+    world.add(Arc::new(Transform::translate(
+        box1,
+        Vec3::new(265., 0., 295.),
+    )));
+
+    let box2 = make_box(
+        &Point3::new(0., 0., 0.),
+        &Point3::new(165., 165., 165.),
+        white,
+    );
+    let box2 = Arc::new(Transform::rotate_y(box2, -18.));
+    world.add(Arc::new(Transform::translate(
+        box2,
+        Vec3::new(130., 0., 65.),
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        lights,
+    )
+}
+fn shapes() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 3., 12.);
+    let lookat = Point3::new(0., 1., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.5,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Disk::new(
+        Point3::new(0., 0., 0.),
+        Vec3::new(0., 1., 0.),
+        8.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+    world.add(Arc::new(Cylinder::new(
+        Point3::new(-3., 0., 0.),
+        Point3::new(-3., 2., 0.),
+        0.8,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2))),
+    )));
+    world.add(Arc::new(Cone::new(
+        Point3::new(0., 0., 0.),
+        Point3::new(0., 2.5, 0.),
+        1.,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 0.1)),
+    )));
+    world.add(make_box(
+        &Point3::new(2.2, 0., -0.8),
+        &Point3::new(3.8, 1.6, 0.8),
+        Arc::new(Lambertian::new(Color::new(0.2, 0.4, 0.8))),
+    ));
+    // A lying cylinder to show arbitrary axes
+    world.add(Arc::new(Cylinder::new(
+        Point3::new(-1.5, 0.4, 2.5),
+        Point3::new(1.5, 0.4, 2.),
+        0.4,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    // Number the objects for the object ID AOV
+    let mut tagged = HittableList::new();
+    for (i, object) in world.objects.iter().enumerate() {
+        tagged.add(Arc::new(ObjectId::new(i as u32 + 1, object.clone())));
+    }
+
+    (
+        camera,
+        BVHNode::new(&mut tagged) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn sdf_shapes() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 2., 9.);
+    let lookat = Point3::new(0., 0.8, 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+    )));
+
+    // A rounded box melting into a sphere, with a dent carved into its front
+    let blob = sdf::smooth_union(
+        sdf::round_box(Vec3::new(0.6, 0.6, 0.6), 0.1),
+        sdf::translate(sdf::sphere(0.5), Vec3::new(0., 0.8, 0.)),
+        0.3,
+    );
+    let blob = sdf::subtraction(
+        blob,
+        sdf::translate(sdf::sphere(0.35), Vec3::new(0., 0., 0.65)),
+    );
+    world.add(Arc::new(Sdf::new(
+        sdf::translate(blob, Vec3::new(-2., 0.6, 0.)),
+        AABB::with_points(&Point3::new(-2.7, 0., -0.7), &Point3::new(-1.3, 2., 0.7)),
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2))),
+    )));
+
+    let bulb = sdf::translate(sdf::mandelbulb(8., 12), Vec3::new(1.5, 1.2, 0.));
+    world.add(Arc::new(
+        Sdf::new(
+            bulb,
+            AABB::with_points(&Point3::new(0.3, 0., -1.2), &Point3::new(2.7, 2.4, 1.2)),
+            Arc::new(Metal::new(Color::new(0.8, 0.7, 0.5), 0.2)),
+        )
+        .with_precision(512, 1e-4),
+    ));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn csg() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 2., 9.);
+    let lookat = Point3::new(0., 1., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.5,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+
+    // A biconvex glass lens is the intersection of two large spheres
+    let glass = Arc::new(Dielectric::new(1.5));
+    let lens = Csg::intersection(
+        Arc::new(Sphere::new(Point3::new(-1.5, 1.2, -2.), 2.5, glass.clone())),
+        Arc::new(Sphere::new(Point3::new(-1.5, 1.2, 2.), 2.5, glass)),
+    );
+    world.add(Arc::new(lens));
+
+    // A sphere with a cylindrical hole drilled through it
+    let red = Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.1)));
+    let drilled = Csg::difference(
+        Arc::new(Sphere::new(Point3::new(1.8, 1.2, 0.), 1.2, red.clone())),
+        Arc::new(Cylinder::new(
+            Point3::new(1.8, 1.2, -2.),
+            Point3::new(1.8, 1.2, 2.),
+            0.5,
+            red,
+        )),
+    );
+    world.add(Arc::new(drilled));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn terrain() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 9., 18.);
+    let lookat = Point3::new(0., 1., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(40.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.4, 0.6, 0.3)));
+    let hills = Heightfield::from_fn(
+        128,
+        128,
+        Point3::new(-10., 0., -10.),
+        Vec3::new(20., 1., 20.),
+        ground,
+        |u, v| {
+            let (x, z) = (u * 2. * consts::PI, v * 2. * consts::PI);
+            1. + f64::sin(3. * x) * f64::cos(2. * z) + 0.5 * f64::sin(7. * x + 5. * z)
+        },
+    );
+    world.add(Arc::new(hills));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., 4.5, 0.),
+        1.,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.)),
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+
+fn adaptive() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let (camera, world, lights) = shapes();
+    let camera = CameraBuilder::from_camera(&camera)
+        // Most of the frame is sky, spend the samples on the objects instead
+        .adaptive_sampling(Some(AdaptiveSampling {
+            min_samples: 16,
+            max_samples: 400,
+            threshold: 0.01,
+        }))
+        .build()
+        .expect("invalid camera settings");
+    (camera, world, lights)
+}
+
+// The shapes scene seen from equal angles to all three axes, without perspective
+fn isometric() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let (camera, world, lights) = shapes();
+    let lookat = Point3::new(0., 0.8, 0.);
+    let camera = CameraBuilder::from_camera(&camera)
+        .lookat(lookat)
+        .lookfrom(lookat + Vec3::new(10., 10., 10.))
+        .projection(Projection::Orthographic { height: 8. })
+        .build()
+        .expect("invalid camera settings");
+    (camera, world, lights)
+}
+
+// 360 degree capture of the shapes scene from among the objects, usable as an environment map
+fn panorama() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let (camera, world, lights) = shapes();
+    let camera = CameraBuilder::from_camera(&camera)
+        .aspect_ratio(2.)
+        .lookfrom(Point3::new(0., 1., 2.))
+        .projection(Projection::Equirectangular)
+        .build()
+        .expect("invalid camera settings");
+    (camera, world, lights)
+}
+
+// The shapes scene through a 180 degree fisheye lens looking straight down
+fn fisheye() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let (camera, world, lights) = shapes();
+    let camera = CameraBuilder::from_camera(&camera)
+        .aspect_ratio(1.)
+        .lookfrom(Point3::new(0., 4., 0.))
+        .lookat(Point3::new(0., 0., 0.))
+        .vup(Vec3::new(0., 0., -1.))
+        .projection(Projection::Fisheye {
+            fov: 180.,
+            mapping: FisheyeMapping::Equisolid,
+        })
+        .build()
+        .expect("invalid camera settings");
+    (camera, world, lights)
+}
+
+// A box tumbling along a spline, blurred over the shutter interval of the camera
+fn keyframes() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 3., 12.);
+    let lookat = Point3::new(0., 1., 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        // A short exposure around the top of the arc
+        .shutter(0.35, 0.45)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.5,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+
+    let cube = make_box(
+        &Point3::new(-0.5, -0.5, -0.5),
+        &Point3::new(0.5, 0.5, 0.5),
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1))),
+    );
+    let track = Track::new(
+        vec![
+            Keyframe::new(0., Vec3::new(-3., 0.5, 0.), Vec3::new(0., 0., 0.)),
+            Keyframe::new(0.4, Vec3::new(-1., 2.5, 0.), Vec3::new(0., 45., -90.)),
+            Keyframe::new(0.7, Vec3::new(1., 0.5, 0.), Vec3::new(0., 90., -180.)),
+            Keyframe::new(1., Vec3::new(3., 1.5, 0.), Vec3::new(0., 135., -270.)),
+        ],
+        Interpolation::Spline,
+    );
+    // Bounded over more than the shutter interval, so the camera can be given another one
+    world.add(Arc::new(Animated::new(cube, track, 0., 2.)));
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
 }
-fn mike() -> (Camera, Arc<dyn Hittable>) {
+
+fn dispersion() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 2., 9.);
+    let lookat = Point3::new(0., 0.9, 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .samples_per_pixel(200)
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        // The index of refraction of the glass depends on the wavelength only in spectral mode
+        .spectral(true)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.25,
+        &Color::new(0.05, 0.05, 0.05),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+
+    // From left to right: no dispersion, crown glass and diamond
+    let iors = [Ior::Constant(1.5168), Ior::bk7(), Ior::diamond()];
+    for (i, ior) in iors.into_iter().enumerate() {
+        world.add(Arc::new(Sphere::new(
+            Point3::new(2.4 * (i as f64 - 1.), 1., 0.),
+            1.,
+            Arc::new(Dielectric::with_ior(ior)),
+        )));
+    }
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+
+// The example model from models/pyramid.obj, loaded as a triangle mesh
+fn pyramid() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(4., 3., 6.);
+    let lookat = Point3::new(0., 0.6, 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.5,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+    world.add(Arc::new(Mesh::new(
+        "pyramid.obj",
+        Arc::new(Metal::new(Color::new(0.8, 0.5, 0.3), 0.2)),
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+
+fn tori() -> (Camera, Arc<dyn Hittable>, HittableList) {
+    let lookfrom = Point3::new(0., 4., 10.);
+    let lookat = Point3::new(0., 0.8, 0.);
+    let vup = Vec3::new(0., 1., 0.);
+    let camera = Camera::builder()
+        .vfov(30.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .build()
+        .expect("invalid camera settings");
+
+    let mut world = HittableList::new();
+    let checker = Arc::new(CheckerTexture::with_color(
+        0.5,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    world.add(Arc::new(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Arc::new(Lambertian::with_texture(checker)),
+    )));
+
+    // A donut lying on the ground
+    world.add(Arc::new(Torus::new(
+        Point3::new(-2.2, 0.4, 0.),
+        1.,
+        0.4,
+        Arc::new(Lambertian::new(Color::new(0.9, 0.45, 0.6))),
+    )));
+
+    // A standing gold ring
+    let gold = Arc::new(Metal::new(Color::new(0.9, 0.7, 0.3), 0.05));
+    world.add(Arc::new(Transform::translate(
+        Arc::new(Transform::rotate(
+            Arc::new(Torus::new(Point3::new(0., 0., 0.), 1., 0.15, gold)),
+            Vec3::new(1., 0., 0.),
+            90.,
+        )),
+        Vec3::new(0.6, 1.15, -0.5),
+    )));
+
+    // A thin glass ring
+    world.add(Arc::new(Torus::new(
+        Point3::new(2.6, 0.25, 0.8),
+        0.9,
+        0.25,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
+}
+fn mike() -> (Camera, Arc<dyn Hittable>, HittableList) {
     let lookfrom = Point3::new(0., 0., 12.);
     let lookat = Point3::new(0., 0., 0.);
     let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(400, 16. / 9., 100, 50, 20., lookfrom, lookat, vup, 0., 12.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .focus_dist(12.)
+        .build()
+        .expect("invalid camera settings");
 
     let mut world = HittableList::new();
     let earth_texture = Arc::new(ImageTexture::new("cat.jpg"));
@@ -67,13 +905,24 @@ fn mike() -> (Camera, Arc<dyn Hittable>) {
     let globe = Arc::new(Sphere::new(Point3::new(0., 0., 0.), 2., earth_surface));
     world.add(globe);
 
-    (camera, BVHNode::new(&mut world) as Arc<dyn Hittable>)
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
 }
-fn earth() -> (Camera, Arc<dyn Hittable>) {
+fn earth() -> (Camera, Arc<dyn Hittable>, HittableList) {
     let lookfrom = Point3::new(0., 0., 12.);
     let lookat = Point3::new(0., 0., 0.);
     let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(400, 16. / 9., 100, 50, 20., lookfrom, lookat, vup, 0., 12.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .focus_dist(12.)
+        .build()
+        .expect("invalid camera settings");
 
     let mut world = HittableList::new();
     let earth_texture = Arc::new(ImageTexture::new("earthmap.jpg"));
@@ -81,14 +930,25 @@ fn earth() -> (Camera, Arc<dyn Hittable>) {
     let globe = Arc::new(Sphere::new(Point3::new(0., 0., 0.), 2., earth_surface));
     world.add(globe);
 
-    (camera, BVHNode::new(&mut world) as Arc<dyn Hittable>)
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
 }
 
-fn checkered_sphere() -> (Camera, Arc<dyn Hittable>) {
+fn checkered_sphere() -> (Camera, Arc<dyn Hittable>, HittableList) {
     let lookfrom = Point3::new(13., 2., 3.);
     let lookat = Point3::new(0., 0., 0.);
     let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(400, 16. / 9., 100, 50, 20., lookfrom, lookat, vup, 0.6, 10.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .defocus_angle(0.6)
+        .build()
+        .expect("invalid camera settings");
     let mut world = HittableList::new();
 
     let checker = Arc::new(CheckerTexture::with_color(
@@ -107,14 +967,25 @@ fn checkered_sphere() -> (Camera, Arc<dyn Hittable>) {
         10.,
         Arc::new(Lambertian::with_texture(checker)),
     )));
-    (camera, BVHNode::new(&mut world) as Arc<dyn Hittable>)
+    (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    )
 }
 
-fn render_much_sphere() -> (Camera, Arc<dyn Hittable>) {
+fn render_much_sphere() -> (Camera, Arc<dyn Hittable>, HittableList) {
     let lookfrom = Point3::new(13., 2., 3.);
     let lookat = Point3::new(0., 0., 0.);
     let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(400, 16. / 9., 100, 50, 20., lookfrom, lookat, vup, 0.6, 10.);
+    let camera = Camera::builder()
+        .vfov(20.)
+        .lookfrom(lookfrom)
+        .lookat(lookat)
+        .vup(vup)
+        .defocus_angle(0.6)
+        .build()
+        .expect("invalid camera settings");
     let mut world = HittableList::new();
 
     let checker = Arc::new(CheckerTexture::with_color(
@@ -163,7 +1034,11 @@ fn render_much_sphere() -> (Camera, Arc<dyn Hittable>) {
 
     let mat3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
     world.add(Arc::new(Sphere::new(Point3::new(-4., 1., 0.), 1., mat3)));
-    return (camera, BVHNode::new(&mut world) as Arc<dyn Hittable>);
+    return (
+        camera,
+        BVHNode::new(&mut world) as Arc<dyn Hittable>,
+        HittableList::new(),
+    );
 }
 
 fn wide_angle_test() -> HittableList {
@@ -1,22 +1,59 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
+
+use serde::Serialize;
 
 use crate::{
     color::Color,
     hittable::HitRecord,
-    ray::Ray,
+    pdf::{CosinePDF, SpherePDF, PDF},
+    ray::{Point3, Ray},
     texture::{SolidColor, Texture},
     utils::random_double,
     vec3::{dot, unit_vector, Vec3},
 };
 
+/// How a material scatters an incoming ray. Diffuse-like materials hand the integrator a PDF to
+/// sample the outgoing direction from, while specular ones (mirrors, glass) pick the single
+/// outgoing ray themselves since it cannot be importance sampled.
+#[derive(Debug, Clone, Default)]
+pub struct ScatterRecord {
+    pub kind: ScatterKind,
+    pub attenuation: Color,
+    pub pdf: Option<Arc<dyn PDF>>,  // None for specular scattering
+    pub skip_pdf_ray: Ray,          // the outgoing ray when there is no pdf
+    pub wavelength_dependent: bool, // the direction was chosen for the ray's wavelength only
+}
+
+/// Type of bounce, the integrator limits the path depth of each kind separately
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScatterKind {
+    #[default]
+    Diffuse,
+    Specular,     // mirror-like reflection
+    Transmission, // refraction into or out of a surface
+}
+
 pub trait Material: Send + Sync + std::fmt::Debug {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool;
+    // Returns false when the ray is absorbed
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _srec: &mut ScatterRecord) -> bool {
+        false
+    }
+
+    // Density of the material scattering r_in into the direction of `scattered`. Together with
+    // the density the direction was actually sampled with, this weights the path contribution.
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.
+    }
+
+    // Light given off by the material at the hit point, only light sources emit anything
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::default()
+    }
+
+    // Base color of the surface at the hit point, written to the albedo AOV
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::default()
+    }
 }
 
 #[derive(Debug)]
@@ -37,23 +74,20 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        let mut scatter_direction = rec.normal + Vec3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            // Catch degenerate scatter direction where the random_unit_vector is the exact
-            // opposite of the normal, thus producing a 0-vector scatter and can lead to undefined
-            // behaviors
-            scatter_direction = rec.normal
-        }
-        *scattered = Ray::new_tm(rec.p, scatter_direction, r_in.time());
-        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
-        return true;
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.kind = ScatterKind::Diffuse;
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Arc::new(CosinePDF::new(&rec.normal)));
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = dot(rec.normal, unit_vector(&scattered.direction()));
+        f64::max(0., cos_theta / PI)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
     }
 }
 
@@ -70,31 +104,89 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
         let mut reflected = Vec3::reflect(&r_in.direction(), &rec.normal);
         reflected = unit_vector(&reflected) + Vec3::random_unit_vector() * self.fuzz;
-        *scattered = Ray::new_tm(rec.p, reflected, r_in.time());
-        *attenuation = self.albedo;
-        return dot(scattered.direction(), rec.normal) > 0.;
+        srec.kind = ScatterKind::Specular;
+        srec.attenuation = self.albedo;
+        srec.pdf = None;
+        srec.skip_pdf_ray = Ray::new_tm(rec.p, reflected, r_in.time());
+        return dot(reflected, rec.normal) > 0.;
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        self.albedo
+    }
+}
+
+/// Index of refraction, optionally depending on the wavelength so that spectral renders show
+/// dispersion. Wavelengths are in nanometres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    Constant(f64),
+    /// n = a + b / λ², with λ in micrometres
+    Cauchy {
+        a: f64,
+        b: f64,
+    },
+    /// n² = 1 + Σ b_i λ² / (λ² - c_i), with λ in micrometres
+    Sellmeier {
+        b: [f64; 3],
+        c: [f64; 3],
+    },
+}
+
+impl Ior {
+    // Catalogues quote indices at the sodium d line, RGB renders use that wavelength
+    const D_LINE: f64 = 587.6;
+
+    /// Borosilicate crown glass, the common optical glass
+    pub fn bk7() -> Self {
+        Ior::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    /// Strongly dispersive, which is where the "fire" of a diamond comes from
+    pub fn diamond() -> Self {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.],
+            c: [0.030625, 0.011236, 0.],
+        }
+    }
+
+    /// Index of refraction at `wavelength`, or at the d line for a wavelength of 0
+    pub fn at(&self, wavelength: f64) -> f64 {
+        let wavelength = if wavelength > 0. {
+            wavelength
+        } else {
+            Self::D_LINE
+        };
+        let um2 = (wavelength / 1000.) * (wavelength / 1000.);
+        match *self {
+            Ior::Constant(n) => n,
+            Ior::Cauchy { a, b } => a + b / um2,
+            Ior::Sellmeier { b, c } => {
+                let n2 = 1. + (0..3).map(|i| b[i] * um2 / (um2 - c[i])).sum::<f64>();
+                f64::sqrt(n2)
+            }
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct Dielectric {
-    refraction_index: f64,
+    ior: Ior,
 }
 
 impl Dielectric {
     pub fn new(ri: f64) -> Self {
-        Dielectric {
-            refraction_index: ri,
-        }
+        Self::with_ior(Ior::Constant(ri))
+    }
+
+    pub fn with_ior(ior: Ior) -> Self {
+        Dielectric { ior }
     }
 
     pub fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
@@ -107,18 +199,15 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(
-        &self,
-        r_in: &Ray,
-        rec: &HitRecord,
-        attenuation: &mut Color,
-        scattered: &mut Ray,
-    ) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.pdf = None;
+        let refraction_index = self.ior.at(r_in.wavelength());
+        srec.wavelength_dependent = !matches!(self.ior, Ior::Constant(_));
         let ri = if rec.front_face {
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
-            self.refraction_index
+            refraction_index
         };
 
         let unit_direction = unit_vector(&r_in.direction());
@@ -128,12 +217,103 @@ impl Material for Dielectric {
         let direction: Vec3;
         if cannot_refract || Dielectric::reflectance(cos_theta, ri) > random_double() {
             // Must reflect
-            direction = Vec3::reflect(&unit_direction, &rec.normal)
+            direction = Vec3::reflect(&unit_direction, &rec.normal);
+            srec.kind = ScatterKind::Specular;
         } else {
             // Must refract
             direction = Vec3::refract(&unit_direction, &rec.normal, ri);
+            srec.kind = ScatterKind::Transmission;
         }
-        *scattered = Ray::new_tm(rec.p, direction, r_in.time());
+        srec.skip_pdf_ray = Ray::new_tm(rec.p, direction, r_in.time());
         true
     }
+
+    // Clear glass lets everything through
+    fn albedo(&self, _rec: &HitRecord) -> Color {
+        Color::new(1., 1., 1.)
+    }
+}
+
+#[derive(Debug)]
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            tex: Arc::new(SolidColor::new(emit)),
+        }
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+// Lights only emit, they do not reflect anything
+impl Material for DiffuseLight {
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.tex.value(u, v, p)
+    }
+
+    // The emitted color, clamped to the range of a reflectance
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        let emitted = self.tex.value(rec.u, rec.v, &rec.p);
+        Color::new(
+            f64::min(emitted.x(), 1.),
+            f64::min(emitted.y(), 1.),
+            f64::min(emitted.z(), 1.),
+        )
+    }
+}
+
+// Phase function of a participating medium, scatters in a uniform random direction
+#[derive(Debug)]
+pub struct Isotropic {
+    tex: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self {
+            tex: Arc::new(SolidColor::new(albedo)),
+        }
+    }
+
+    pub fn with_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.kind = ScatterKind::Diffuse;
+        srec.attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Arc::new(SpherePDF::new()));
+        true
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Color {
+        self.tex.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispersion() {
+        // BK7 is about 1.5168 at the d line, and bends blue light more than red
+        let bk7 = Ior::bk7();
+        assert!((bk7.at(0.) - 1.5168).abs() < 1e-4);
+        assert!(bk7.at(450.) > bk7.at(650.));
+        assert!((Ior::diamond().at(587.6) - 2.417).abs() < 2e-3);
+        assert_eq!(Ior::Constant(1.33).at(400.), 1.33);
+    }
 }
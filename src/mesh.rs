@@ -0,0 +1,109 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    bvh::BVHNode,
+    hittable::{HitRecord, Hittable, HittableList},
+    interval::Interval,
+    material::Material,
+    obj::{ObjModel, ObjVertex},
+    ray::Ray,
+    triangle::Triangle,
+};
+
+/// A triangle mesh with its own BVH, exposed as a single Hittable
+#[derive(Debug)]
+pub struct Mesh {
+    bvh: Arc<dyn Hittable>,
+    bbox: AABB,
+    triangle_count: usize,
+}
+
+impl Mesh {
+    /// Loads an OBJ file from the `models` directory. If the file cannot be loaded the error is
+    /// printed and an empty mesh is returned, the same way `ImageTexture` handles missing images.
+    pub fn new(filename: &str, material: Arc<dyn Material>) -> Self {
+        match ObjModel::load(filename) {
+            Ok(model) => Self::from_model(&model, material),
+            Err(e) => {
+                eprintln!("ERROR: Could not load mesh file '{}': {}", filename, e);
+                Self::from_triangles(Vec::new())
+            }
+        }
+    }
+
+    pub fn from_model(model: &ObjModel, material: Arc<dyn Material>) -> Self {
+        Self::with_group_materials(model, material, &HashMap::new())
+    }
+
+    /// Builds a mesh where the triangles of each named group use the given material, and every
+    /// other group uses `default_material`
+    pub fn with_group_materials(
+        model: &ObjModel,
+        default_material: Arc<dyn Material>,
+        group_materials: &HashMap<String, Arc<dyn Material>>,
+    ) -> Self {
+        let mut triangles: Vec<Arc<dyn Hittable>> = Vec::with_capacity(model.triangle_count());
+        for group in &model.groups {
+            let material = group_materials
+                .get(&group.name)
+                .unwrap_or(&default_material);
+            for face in &group.triangles {
+                triangles.push(Arc::new(Self::make_triangle(model, face, material.clone())));
+            }
+        }
+        Self::from_triangles(triangles)
+    }
+
+    pub fn from_triangles(mut triangles: Vec<Arc<dyn Hittable>>) -> Self {
+        let triangle_count = triangles.len();
+        if triangles.is_empty() {
+            // BVHNode cannot be built over an empty list
+            return Self {
+                bvh: Arc::new(HittableList::new()),
+                bbox: AABB::empty(),
+                triangle_count,
+            };
+        }
+        let bvh = BVHNode::construct(&mut triangles, 0, triangle_count);
+        Self {
+            bbox: bvh.bounding_box(),
+            bvh,
+            triangle_count,
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_count
+    }
+
+    fn make_triangle(
+        model: &ObjModel,
+        face: &[ObjVertex; 3],
+        material: Arc<dyn Material>,
+    ) -> Triangle {
+        let vertices = face.map(|c| model.positions[c.position]);
+        // Vertex attributes are only used if every corner of the face provides them
+        let normals = match face.map(|c| c.normal) {
+            [Some(a), Some(b), Some(c)] => {
+                Some([model.normals[a], model.normals[b], model.normals[c]])
+            }
+            _ => None,
+        };
+        let uvs = match face.map(|c| c.uv) {
+            [Some(a), Some(b), Some(c)] => Some([model.uvs[a], model.uvs[b], model.uvs[c]]),
+            _ => None,
+        };
+        Triangle::with_attributes(vertices, normals, uvs, material)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, ray_t, rec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
@@ -0,0 +1,228 @@
+// Closed-form polynomial root finding, following Jochen Schwarze's "Cubic and Quartic Roots"
+// from Graphics Gems. Coefficients are given from the highest degree down, e.g.
+// solve_quadratic(a, b, c) solves a x^2 + b x + c = 0. Real roots are returned in ascending
+// order; repeated roots may be reported once.
+
+use std::f64::consts::PI;
+
+const EPSILON: f64 = 1e-12;
+
+/// Zero test relative to the magnitude of the terms `x` was computed from. An absolute epsilon
+/// breaks down for rays close to a surface, where the depressed coefficients get very small.
+fn is_zero(x: f64, scale: f64) -> bool {
+    x.abs() <= EPSILON * scale.abs()
+}
+
+fn max_abs(values: &[f64]) -> f64 {
+    values.iter().fold(0., |m, v| f64::max(m, v.abs()))
+}
+
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if is_zero(a, max_abs(&[b, c])) {
+        return solve_linear(b, c);
+    }
+    // Normal form x^2 + px + q = 0
+    let p = b / (2. * a);
+    let q = c / a;
+    let discriminant = p * p - q;
+
+    if is_zero(discriminant, f64::max(p * p, q.abs())) {
+        vec![-p]
+    } else if discriminant < 0. {
+        vec![]
+    } else {
+        let sqrt_d = f64::sqrt(discriminant);
+        vec![-sqrt_d - p, sqrt_d - p]
+    }
+}
+
+fn solve_linear(a: f64, b: f64) -> Vec<f64> {
+    if a == 0. {
+        vec![]
+    } else {
+        vec![-b / a]
+    }
+}
+
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if is_zero(a, max_abs(&[b, c, d])) {
+        return solve_quadratic(b, c, d);
+    }
+    // Normal form x^3 + Ax^2 + Bx + C = 0
+    let (a, b, c) = (b / a, c / a, d / a);
+
+    // Substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3. + b) / 3.;
+    let q = (2. / 27. * a * sq_a - a * b / 3. + c) / 2.;
+
+    // Cardano's formula
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let mut roots = if is_zero(discriminant, f64::max(q * q, cb_p.abs())) {
+        if is_zero(q, max_abs(&[a * sq_a, a * b, c])) {
+            // One triple solution
+            vec![0.]
+        } else {
+            // One single and one double solution
+            let u = f64::cbrt(-q);
+            vec![2. * u, -u]
+        }
+    } else if discriminant < 0. {
+        // Three real solutions
+        let phi = f64::acos(f64::clamp(-q / f64::sqrt(-cb_p), -1., 1.)) / 3.;
+        let t = 2. * f64::sqrt(-p);
+        vec![
+            t * f64::cos(phi),
+            -t * f64::cos(phi + PI / 3.),
+            -t * f64::cos(phi - PI / 3.),
+        ]
+    } else {
+        // One real solution
+        let sqrt_d = f64::sqrt(discriminant);
+        vec![f64::cbrt(sqrt_d - q) - f64::cbrt(sqrt_d + q)]
+    };
+
+    // Resubstitute
+    for root in roots.iter_mut() {
+        *root -= a / 3.;
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if is_zero(a, max_abs(&[b, c, d, e])) {
+        return solve_cubic(b, c, d, e);
+    }
+    let coeffs = [a, b, c, d, e];
+    // Normal form x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let (a, b, c, d) = (b / a, c / a, d / a, e / a);
+
+    // Substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3. / 8. * sq_a + b;
+    let q = 1. / 8. * sq_a * a - 0.5 * a * b + c;
+    let r = -3. / 256. * sq_a * sq_a + 1. / 16. * sq_a * b - 0.25 * a * c + d;
+
+    let r_scale = max_abs(&[sq_a * sq_a, sq_a * b, a * c, d]);
+    let mut roots = if is_zero(r, r_scale) {
+        // No absolute term: y(y^3 + py + q) = 0
+        let mut roots = solve_cubic(1., 0., p, q);
+        roots.push(0.);
+        roots
+    } else {
+        // Solve the resolvent cubic and take its largest real solution, the one that keeps
+        // the square roots below real
+        let resolvent = solve_cubic(1., -0.5 * p, -r, 0.5 * r * p - 0.125 * q * q);
+        let z = resolvent[resolvent.len() - 1];
+
+        // ... to build two quadratic equations
+        let u = z * z - r;
+        let v = 2. * z - p;
+        let u = if is_zero(u, f64::max(z * z, r.abs())) {
+            0.
+        } else if u > 0. {
+            f64::sqrt(u)
+        } else {
+            return vec![];
+        };
+        let v = if is_zero(v, f64::max(2. * z.abs(), p.abs())) {
+            0.
+        } else if v > 0. {
+            f64::sqrt(v)
+        } else {
+            return vec![];
+        };
+
+        let mut roots = solve_quadratic(1., if q < 0. { -v } else { v }, z - u);
+        roots.extend(solve_quadratic(1., if q < 0. { v } else { -v }, z + u));
+        roots
+    };
+
+    // Resubstitute, then refine with a couple of Newton steps on the original polynomial since
+    // the closed form loses precision when the coefficients differ a lot in magnitude
+    for root in roots.iter_mut() {
+        *root -= 0.25 * a;
+        *root = polish_root(&coeffs, *root);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Newton-Raphson refinement of a root of the polynomial with the given coefficients (highest
+/// degree first)
+pub fn polish_root(coeffs: &[f64], x: f64) -> f64 {
+    let mut x = x;
+    for _ in 0..2 {
+        let (mut value, mut derivative) = (0., 0.);
+        for c in coeffs {
+            derivative = derivative * x + value;
+            value = value * x + c;
+        }
+        if derivative == 0. || !derivative.is_finite() {
+            break;
+        }
+        let next = x - value / derivative;
+        if !next.is_finite() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-7, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic() {
+        assert_roots(solve_quadratic(1., -3., 2.), &[1., 2.]);
+        assert_roots(solve_quadratic(1., 0., 1.), &[]);
+        assert_roots(solve_quadratic(0., 2., -4.), &[2.]);
+    }
+
+    #[test]
+    fn cubic() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(1., -6., 11., -6.), &[1., 2., 3.]);
+        // x^3 - 8 has a single real root
+        assert_roots(solve_cubic(2., 0., 0., -16.), &[2.]);
+    }
+
+    #[test]
+    fn quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        // (x^2 + 1)(x^2 - 4)
+        assert_roots(solve_quartic(3., 0., -9., 0., -12.), &[-2., 2.]);
+        // Widely spread roots (x + 100)(x - 0.01)(x - 0.5)(x - 7)
+        let (r1, r2, r3, r4) = (-100., 0.01, 0.5, 7.);
+        let b = -(r1 + r2 + r3 + r4);
+        let c = r1 * r2 + r1 * r3 + r1 * r4 + r2 * r3 + r2 * r4 + r3 * r4;
+        let d = -(r1 * r2 * r3 + r1 * r2 * r4 + r1 * r3 * r4 + r2 * r3 * r4);
+        let e = r1 * r2 * r3 * r4;
+        assert_roots(solve_quartic(1., b, c, d, e), &[r1, r2, r3, r4]);
+        // Clustered roots, as seen by rays starting right next to a torus:
+        // (x - 0.95)(x - 1.05)(x^2 - 2x + 1.01)
+        assert_roots(
+            solve_quartic(1., -4., 6.0075, -4.015, 1.007475),
+            &[0.95, 1.05],
+        );
+    }
+}
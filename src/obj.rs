@@ -0,0 +1,240 @@
+use std::{fmt, fs, path::PathBuf};
+
+use crate::{ray::Point3, vec3::Vec3};
+
+/// Indices of one face corner into the model's position, texture coordinate and normal lists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct ObjGroup {
+    pub name: String,
+    pub triangles: Vec<[ObjVertex; 3]>,
+}
+
+/// Geometry parsed from a Wavefront OBJ file. Polygons are fan-triangulated, and faces are kept
+/// in the group (`g` or `o` statement) they were declared in.
+#[derive(Debug, Default)]
+pub struct ObjModel {
+    pub positions: Vec<Point3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub normals: Vec<Vec3>,
+    pub groups: Vec<ObjGroup>,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "{}", e),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl ObjModel {
+    /// Loads an OBJ file from the `models` directory
+    pub fn load(filename: &str) -> Result<Self, ObjError> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("models")
+            .join(filename);
+        let src = fs::read_to_string(path).map_err(ObjError::Io)?;
+        Self::parse(&src)
+    }
+
+    pub fn parse(src: &str) -> Result<Self, ObjError> {
+        let mut model = ObjModel::default();
+        let mut current = ObjGroup {
+            name: String::from("default"),
+            triangles: Vec::new(),
+        };
+
+        for (n, line) in src.lines().enumerate() {
+            let line_no = n + 1;
+            let parse_err = |message: &str| ObjError::Parse {
+                line: line_no,
+                message: message.to_string(),
+            };
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            match keyword {
+                "v" => {
+                    let c =
+                        parse_floats::<3>(&mut tokens).ok_or_else(|| parse_err("bad vertex"))?;
+                    model.positions.push(Point3::new(c[0], c[1], c[2]));
+                }
+                "vn" => {
+                    let c =
+                        parse_floats::<3>(&mut tokens).ok_or_else(|| parse_err("bad normal"))?;
+                    model.normals.push(Vec3::new(c[0], c[1], c[2]));
+                }
+                "vt" => {
+                    // The optional third texture coordinate is ignored
+                    let c = parse_floats::<2>(&mut tokens)
+                        .ok_or_else(|| parse_err("bad texture coordinate"))?;
+                    model.uvs.push((c[0], c[1]));
+                }
+                "f" => {
+                    let corners = tokens
+                        .map(|t| model.parse_vertex(t))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| parse_err("bad face index"))?;
+                    if corners.len() < 3 {
+                        return Err(parse_err("face with fewer than three vertices"));
+                    }
+                    // Triangulate the polygon as a fan around its first vertex
+                    for i in 1..corners.len() - 1 {
+                        current
+                            .triangles
+                            .push([corners[0], corners[i], corners[i + 1]]);
+                    }
+                }
+                "g" | "o" => {
+                    let name = tokens.collect::<Vec<_>>().join(" ");
+                    let previous = std::mem::replace(
+                        &mut current,
+                        ObjGroup {
+                            name,
+                            triangles: Vec::new(),
+                        },
+                    );
+                    if !previous.triangles.is_empty() {
+                        model.groups.push(previous);
+                    }
+                }
+                // Comments, materials, smoothing groups and other statements are not supported
+                _ => {}
+            }
+        }
+        if !current.triangles.is_empty() {
+            model.groups.push(current);
+        }
+        Ok(model)
+    }
+
+    /// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner. OBJ indices start at 1, and
+    /// negative indices are relative to the end of the lists read so far.
+    fn parse_vertex(&self, token: &str) -> Option<ObjVertex> {
+        let mut parts = token.split('/');
+        let position = resolve_index(parts.next()?, self.positions.len())?;
+        let uv = match parts.next() {
+            Some(s) if !s.is_empty() => Some(resolve_index(s, self.uvs.len())?),
+            _ => None,
+        };
+        let normal = match parts.next() {
+            Some(s) if !s.is_empty() => Some(resolve_index(s, self.normals.len())?),
+            _ => None,
+        };
+        Some(ObjVertex {
+            position,
+            uv,
+            normal,
+        })
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.groups.iter().map(|g| g.triangles.len()).sum()
+    }
+}
+
+fn resolve_index(s: &str, len: usize) -> Option<usize> {
+    let i: i64 = s.parse().ok()?;
+    let index = match i {
+        i if i > 0 => i - 1,
+        i if i < 0 => len as i64 + i,
+        _ => return None,
+    };
+    if index < 0 || index >= len as i64 {
+        return None;
+    }
+    Some(index as usize)
+}
+
+fn parse_floats<'a, const N: usize>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Option<[f64; N]> {
+    let mut values = [0.; N];
+    for value in values.iter_mut() {
+        *value = tokens.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_FACE: &str = "
+# two groups sharing vertices
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g front
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g back
+f -1//1 -2//1 -3//1
+";
+
+    #[test]
+    fn parses_groups_and_triangulates() {
+        let model = ObjModel::parse(CUBE_FACE).unwrap();
+        assert_eq!(model.positions.len(), 4);
+        assert_eq!(model.uvs.len(), 4);
+        assert_eq!(model.normals.len(), 1);
+        assert_eq!(model.groups.len(), 2);
+        assert_eq!(model.groups[0].name, "front");
+        assert_eq!(model.groups[0].triangles.len(), 2);
+        assert_eq!(model.triangle_count(), 3);
+
+        let back = model.groups[1].triangles[0];
+        assert_eq!(back[0].position, 3);
+        assert_eq!(back[0].uv, None);
+        assert_eq!(back[0].normal, Some(0));
+    }
+
+    #[test]
+    fn loads_the_example_model() {
+        let model = ObjModel::load("pyramid.obj").unwrap();
+        assert_eq!(model.positions.len(), 5);
+        assert_eq!(model.uvs.len(), 5);
+        assert_eq!(model.normals.len(), 5);
+        let names: Vec<&str> = model.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["base", "sides"]);
+        assert_eq!(model.groups[0].triangles.len(), 2);
+        assert_eq!(model.groups[1].triangles.len(), 4);
+
+        // Relative normal indices pick the normal declared right before each face
+        for (side, face) in model.groups[1].triangles.iter().enumerate() {
+            assert!(face.iter().all(|c| c.normal == Some(side + 1)));
+        }
+        // The last side is written with negative indices only
+        let last = model.groups[1].triangles[3];
+        assert_eq!(last.map(|c| c.position), [0, 3, 4]);
+        assert_eq!(last.map(|c| c.uv), [Some(0), Some(1), Some(4)]);
+    }
+
+    #[test]
+    fn rejects_out_of_range_index() {
+        let err = ObjModel::parse("v 0 0 0\nf 1 2 3\n").unwrap_err();
+        assert!(matches!(err, ObjError::Parse { line: 2, .. }));
+    }
+}
@@ -0,0 +1,46 @@
+use crate::vec3::{cross, dot, unit_vector, Vec3};
+
+/// Orthonormal basis built around a given direction, which becomes the local z (w) axis
+#[derive(Debug, Clone, Copy)]
+pub struct ONB {
+    axis: [Vec3; 3],
+}
+
+impl ONB {
+    pub fn new(n: &Vec3) -> Self {
+        let w = unit_vector(n);
+        // Pick any helper vector that is not parallel to w
+        let a = if f64::abs(w.x()) > 0.9 {
+            Vec3::new(0., 1., 0.)
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let v = unit_vector(&cross(w, a));
+        let u = cross(w, v);
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    /// Transform from basis coordinates to world space
+    pub fn transform(&self, v: &Vec3) -> Vec3 {
+        self.axis[0] * v[0] + self.axis[1] * v[1] + self.axis[2] * v[2]
+    }
+
+    /// Transform from world space to basis coordinates
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            dot(*v, self.axis[0]),
+            dot(*v, self.axis[1]),
+            dot(*v, self.axis[2]),
+        )
+    }
+}
@@ -0,0 +1,150 @@
+use std::{f64::consts::PI, fmt::Debug, sync::Arc};
+
+use crate::{
+    hittable::Hittable,
+    onb::ONB,
+    ray::Point3,
+    sampler::{self, square_to_cosine_hemisphere, square_to_sphere},
+    utils::random_double,
+    vec3::{dot, unit_vector, Vec3},
+};
+
+/// Probability density over directions, measured with respect to solid angle. `generate`
+/// draws a direction distributed according to the density reported by `value`.
+pub trait PDF: Send + Sync + Debug {
+    fn value(&self, direction: &Vec3) -> f64;
+    fn generate(&self) -> Vec3;
+}
+
+/// Uniform density over all directions
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpherePDF;
+
+impl SpherePDF {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl PDF for SpherePDF {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let (u, v) = sampler::get_2d();
+        let (x, y, z) = square_to_sphere(u, v);
+        Vec3::new(x, y, z)
+    }
+}
+
+/// Density proportional to the cosine of the angle with a normal, which matches the Lambertian
+/// scattering distribution exactly
+#[derive(Debug, Clone, Copy)]
+pub struct CosinePDF {
+    uvw: ONB,
+}
+
+impl CosinePDF {
+    pub fn new(w: &Vec3) -> Self {
+        Self { uvw: ONB::new(w) }
+    }
+}
+
+impl PDF for CosinePDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine_theta = dot(unit_vector(direction), self.uvw.w());
+        f64::max(0., cosine_theta / PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let (u, v) = sampler::get_2d();
+        let (x, y, z) = square_to_cosine_hemisphere(u, v);
+        self.uvw.transform(&Vec3::new(x, y, z))
+    }
+}
+
+/// Directions from `origin` towards a Hittable, see `Hittable::pdf_value` and
+/// `Hittable::random`
+#[derive(Debug, Clone)]
+pub struct HittablePDF {
+    objects: Arc<dyn Hittable>,
+    origin: Point3,
+}
+
+impl HittablePDF {
+    pub fn new(objects: Arc<dyn Hittable>, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl PDF for HittablePDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.objects.random(&self.origin)
+    }
+}
+
+/// Even blend of two densities
+#[derive(Debug, Clone)]
+pub struct MixturePDF {
+    p: [Arc<dyn PDF>; 2],
+}
+
+impl MixturePDF {
+    pub fn new(p0: Arc<dyn PDF>, p1: Arc<dyn PDF>) -> Self {
+        Self { p: [p0, p1] }
+    }
+}
+
+impl PDF for MixturePDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double() < 0.5 {
+            self.p[0].generate()
+        } else {
+            self.p[1].generate()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Monte Carlo estimate of the integral of a density over the sphere of directions, which
+    // should be one
+    fn integral(pdf: &dyn PDF) -> f64 {
+        let n = 200_000;
+        let uniform = SpherePDF::new();
+        let sum: f64 = (0..n)
+            .map(|_| pdf.value(&uniform.generate()) / uniform.value(&Vec3::default()))
+            .sum();
+        sum / n as f64
+    }
+
+    #[test]
+    fn densities_integrate_to_one() {
+        let cosine = Arc::new(CosinePDF::new(&Vec3::new(0., 1., 1.)));
+        assert!((integral(cosine.as_ref()) - 1.).abs() < 0.02);
+        let mixture = MixturePDF::new(cosine.clone(), Arc::new(SpherePDF::new()));
+        assert!((integral(&mixture) - 1.).abs() < 0.02);
+    }
+
+    #[test]
+    fn cosine_samples_stay_in_hemisphere() {
+        let normal = Vec3::new(1., -2., 0.5);
+        let pdf = CosinePDF::new(&normal);
+        for _ in 0..1000 {
+            let direction = pdf.generate();
+            assert!(dot(direction, normal) >= 0.);
+            assert!(pdf.value(&direction) > 0.);
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use rand::{Rng, SeedableRng};
+
 use crate::{
     ray::Point3,
-    utils::{random_double, random_int},
+    utils::{with_rng, Pcg32},
 };
 
 const POINT_COUNT: usize = 256;
@@ -15,16 +17,25 @@ pub struct Perlin {
 
 impl Perlin {
     pub fn new() -> Self {
+        with_rng(Self::from_rng)
+    }
+
+    /// Noise that only depends on `seed`, not on the random numbers drawn before it
+    pub fn with_seed(seed: u64) -> Self {
+        Self::from_rng(&mut Pcg32::seed_from_u64(seed))
+    }
+
+    pub fn from_rng(rng: &mut impl Rng) -> Self {
         let mut randfloat = [0.; POINT_COUNT];
         for i in 0..POINT_COUNT {
-            randfloat[i] = random_double()
+            randfloat[i] = rng.gen::<f64>()
         }
         let mut perm_x = [0; POINT_COUNT];
         let mut perm_y = [0; POINT_COUNT];
         let mut perm_z = [0; POINT_COUNT];
-        Self::generate_perm(&mut perm_x);
-        Self::generate_perm(&mut perm_y);
-        Self::generate_perm(&mut perm_z);
+        Self::generate_perm(&mut perm_x, rng);
+        Self::generate_perm(&mut perm_y, rng);
+        Self::generate_perm(&mut perm_z, rng);
         Self {
             randfloat,
             perm_x,
@@ -32,16 +43,16 @@ impl Perlin {
             perm_z,
         }
     }
-    fn generate_perm(p: &mut [i32]) {
+    fn generate_perm(p: &mut [i32], rng: &mut impl Rng) {
         for i in 0..POINT_COUNT {
             p[i] = i as i32;
         }
-        Self::permute(p, POINT_COUNT);
+        Self::permute(p, POINT_COUNT, rng);
     }
 
-    fn permute(p: &mut [i32], n: usize) {
+    fn permute(p: &mut [i32], n: usize, rng: &mut impl Rng) {
         for i in (0..n).rev() {
-            let target = random_int(0, i as i32) as usize;
+            let target = rng.gen_range(0..=i);
             let tmp = p[i];
             p[i] = p[target];
             p[target] = tmp;
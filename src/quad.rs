@@ -0,0 +1,218 @@
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable, HittableList},
+    interval::Interval,
+    material::Material,
+    ray::{Point3, Ray},
+    utils::random_double,
+    vec3::{cross, dot, unit_vector, Vec3},
+};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Quad {
+    q: Point3, // starting corner
+    u: Vec3,   // first edge vector
+    v: Vec3,   // second edge vector
+    w: Vec3,   // cached n / (n . n), used to find the planar coordinates of a hit
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+    normal: Vec3,
+    d: f64, // plane constant in Ax + By + Cz = D
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = cross(u, v);
+        let normal = unit_vector(&n);
+        let d = dot(normal, q);
+        let w = n / dot(n, n);
+
+        // Bounding box of all four vertices, both diagonals are needed since the quad can be
+        // oriented in any way
+        let bbox_diagonal1 = AABB::with_points(&q, &(q + u + v));
+        let bbox_diagonal2 = AABB::with_points(&(q + u), &(q + v));
+        Self {
+            q,
+            u,
+            v,
+            w,
+            material: Some(material),
+            bbox: AABB::with_boxes(&bbox_diagonal1, &bbox_diagonal2),
+            normal,
+            d,
+            area: n.length(),
+        }
+    }
+
+    /// Given the hit point in plane coordinates, return false if it is outside the primitive,
+    /// otherwise set the hit record UV coordinates and return true.
+    fn is_interior(a: f64, b: f64, rec: &mut HitRecord) -> bool {
+        let unit_interval = Interval::new(0., 1.);
+        if !unit_interval.contains(a) || !unit_interval.contains(b) {
+            return false;
+        }
+        rec.u = a;
+        rec.v = b;
+        true
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let denom = dot(self.normal, r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if f64::abs(denom) < 1e-8 {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval
+        let t = (self.d - dot(self.normal, r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = dot(self.w, cross(planar_hitpt_vector, self.v));
+        let beta = dot(self.w, cross(self.u, planar_hitpt_vector));
+        if !Self::is_interior(alpha, beta, rec) {
+            return false;
+        }
+
+        // Ray hits the 2D shape, set the rest of the hit record
+        rec.t = t;
+        rec.p = intersection;
+        rec.material = self.material.clone();
+        rec.set_face_normal(r, &self.normal);
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+        ) {
+            return 0.;
+        }
+
+        // Convert the uniform density over the area into a density over solid angle
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = f64::abs(dot(*direction, rec.normal) / direction.length());
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let p = self.q + (self.u * random_double()) + (self.v * random_double());
+        p - *origin
+    }
+}
+
+/// Returns the 3D box (six sides) that contains the two opposite vertices a & b
+pub fn make_box(a: &Point3, b: &Point3, material: Arc<dyn Material>) -> Arc<HittableList> {
+    let mut sides = HittableList::new();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates
+    let min = Point3::new(
+        f64::min(a.x(), b.x()),
+        f64::min(a.y(), b.y()),
+        f64::min(a.z(), b.z()),
+    );
+    let max = Point3::new(
+        f64::max(a.x(), b.x()),
+        f64::max(a.y(), b.y()),
+        f64::max(a.z(), b.z()),
+    );
+
+    let dx = Vec3::new(max.x() - min.x(), 0., 0.);
+    let dy = Vec3::new(0., max.y() - min.y(), 0.);
+    let dz = Vec3::new(0., 0., max.z() - min.z());
+
+    let faces = [
+        (Point3::new(min.x(), min.y(), max.z()), dx, dy), // front
+        (Point3::new(max.x(), min.y(), max.z()), -dz, dy), // right
+        (Point3::new(max.x(), min.y(), min.z()), -dx, dy), // back
+        (Point3::new(min.x(), min.y(), min.z()), dz, dy), // left
+        (Point3::new(min.x(), max.y(), max.z()), dx, -dz), // top
+        (Point3::new(min.x(), min.y(), min.z()), dx, dz), // bottom
+    ];
+    for (q, u, v) in faces {
+        sides.add(Arc::new(Quad::new(q, u, v, material.clone())));
+    }
+
+    Arc::new(sides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{grey, hit};
+
+    fn unit_quad() -> Quad {
+        Quad::new(
+            Point3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            grey(),
+        )
+    }
+
+    #[test]
+    fn hit_inside_sets_uv() {
+        let r = Ray::new(Point3::new(0.25, 0.75, 1.), Vec3::new(0., 0., -1.));
+        let rec = hit(&unit_quad(), &r).unwrap();
+        assert_eq!(rec.t, 1.);
+        assert_eq!(rec.u, 0.25);
+        assert_eq!(rec.v, 0.75);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn miss_outside_and_parallel() {
+        let quad = unit_quad();
+        let outside = Ray::new(Point3::new(1.5, 0.5, 1.), Vec3::new(0., 0., -1.));
+        assert!(hit(&quad, &outside).is_none());
+        let parallel = Ray::new(Point3::new(0.5, 0.5, 1.), Vec3::new(1., 0., 0.));
+        assert!(hit(&quad, &parallel).is_none());
+    }
+
+    #[test]
+    fn flat_bounding_box_is_padded() {
+        let bbox = unit_quad().bounding_box();
+        assert!(bbox.axis_interval(2).size() > 0.);
+    }
+
+    #[test]
+    fn box_sides_enclose_the_corners() {
+        let cube = make_box(&Point3::new(1., 2., 3.), &Point3::new(0., 0., 0.), grey());
+        assert_eq!(cube.objects.len(), 6);
+        let bbox = cube.bounding_box();
+        for (axis, max) in [(0, 1.), (1, 2.), (2, 3.)] {
+            assert!(bbox.axis_interval(axis).min.abs() < 1e-3);
+            assert!((bbox.axis_interval(axis).max - max).abs() < 1e-3);
+        }
+
+        // From outside the front face is hit first, from inside the far side is hit from behind
+        let outside = Ray::new(Point3::new(0.5, 1., 10.), Vec3::new(0., 0., -1.));
+        let rec = hit(cube.as_ref(), &outside).unwrap();
+        assert_eq!(rec.t, 7.);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, Vec3::new(0., 0., 1.));
+        let inside = Ray::new(Point3::new(0.5, 1., 1.), Vec3::new(0., 0., -1.));
+        let rec = hit(cube.as_ref(), &inside).unwrap();
+        assert_eq!(rec.t, 1.);
+        assert!(!rec.front_face);
+
+        let beside = Ray::new(Point3::new(1.5, 1., 10.), Vec3::new(0., 0., -1.));
+        assert!(hit(cube.as_ref(), &beside).is_none());
+    }
+}
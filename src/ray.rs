@@ -2,24 +2,36 @@ use crate::vec3::Vec3;
 
 pub type Point3 = Vec3;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Ray {
     orig: Point3,
     dir: Vec3,
     tm: f64,
+    wavelength: f64, // in nanometres when rendering spectrally, 0 otherwise
 }
 
 impl Ray {
     pub fn new(orig: Point3, dir: Vec3) -> Self {
-        Self { orig, dir, tm: 0. }
+        Self::new_tm(orig, dir, 0.)
     }
     pub fn new_tm(orig: Point3, dir: Vec3, tm: f64) -> Self {
-        Self { orig, dir, tm }
+        Self {
+            orig,
+            dir,
+            tm,
+            wavelength: 0.,
+        }
+    }
+    pub fn with_wavelength(self, wavelength: f64) -> Self {
+        Self { wavelength, ..self }
     }
 
     pub fn time(&self) -> f64 {
         self.tm
     }
+    pub fn wavelength(&self) -> f64 {
+        self.wavelength
+    }
     pub fn origin(&self) -> Point3 {
         self.orig
     }
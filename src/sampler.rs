@@ -0,0 +1,349 @@
+use std::{cell::Cell, f64::consts::PI};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{random_double, sample_seed, with_seed};
+
+/// How the sample points of a pixel are spread over the pixel, the lens, the shutter interval
+/// and the BSDF directions. Everything but `Independent` places the samples of a pixel so they
+/// cover the sampling domain more evenly than independent random points, which lowers noise.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SamplerKind {
+    #[default]
+    Independent,
+    Stratified, // jittered grid, best when the number of samples is a square number
+    Halton,     // radical inverses in prime bases, randomly shifted per pixel
+    Sobol,      // Owen scrambled (0,2)-sequence, padded with shuffled copies per dimension pair
+}
+
+// The sampler works like the random number generator in utils: the camera starts a sample
+// before tracing it, and the sampling routines draw the next dimensions of that sample from the
+// current thread's state.
+#[derive(Debug, Clone, Copy)]
+struct SampleState {
+    kind: SamplerKind,
+    seed: u64,  // decorrelates pixels
+    index: u64, // sample index within the pixel
+    samples_per_pixel: u64,
+    dimension: u64,
+}
+
+thread_local! {
+    static STATE: Cell<SampleState> = const {
+        Cell::new(SampleState {
+            kind: SamplerKind::Independent,
+            seed: 0,
+            index: 0,
+            samples_per_pixel: 1,
+            dimension: 0,
+        })
+    };
+}
+
+// Halton dimensions available before falling back to random numbers
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Starts sample `index` (out of `samples_per_pixel`) of a pixel
+pub fn start_sample(kind: SamplerKind, seed: u64, pixel: u64, index: u64, samples_per_pixel: u64) {
+    STATE.with(|state| {
+        state.set(SampleState {
+            kind,
+            seed: sample_seed(seed, pixel, u64::MAX),
+            index,
+            samples_per_pixel: u64::max(samples_per_pixel, 1),
+            dimension: 0,
+        })
+    });
+}
+
+/// Runs `f` as sample `index` of a pixel, on the random stream of that sample, and restores the
+/// sampler and random stream the current thread had before
+pub fn with_sample<T>(
+    kind: SamplerKind,
+    seed: u64,
+    pixel: u64,
+    index: u64,
+    samples_per_pixel: u64,
+    f: impl FnOnce() -> T,
+) -> T {
+    let saved = STATE.with(|state| state.get());
+    start_sample(kind, seed, pixel, index, samples_per_pixel);
+    let result = with_seed(sample_seed(seed, pixel, index), f);
+    STATE.with(|state| state.set(saved));
+    result
+}
+
+/// Next dimension of the current sample, in [0,1)
+pub fn get_1d() -> f64 {
+    let state = next_dimensions(1);
+    match state.kind {
+        SamplerKind::Independent => random_double(),
+        SamplerKind::Stratified => {
+            let strata = state.samples_per_pixel;
+            let stratum = permutation_element(
+                state.index % strata,
+                strata,
+                dimension_hash(&state, 0) as u32,
+            );
+            (stratum as f64 + random_double()) / strata as f64
+        }
+        SamplerKind::Halton => halton(&state, 0),
+        SamplerKind::Sobol => sobol_2d(&state).0,
+    }
+}
+
+/// Next two dimensions of the current sample, in [0,1)^2
+pub fn get_2d() -> (f64, f64) {
+    let state = next_dimensions(2);
+    match state.kind {
+        SamplerKind::Independent => (random_double(), random_double()),
+        SamplerKind::Stratified => {
+            // Grid with one cell per sample, the cells are visited in a random order that
+            // differs per pixel and per dimension
+            let nx = f64::ceil(f64::sqrt(state.samples_per_pixel as f64)) as u64;
+            let ny = state.samples_per_pixel.div_ceil(nx);
+            let cell = permutation_element(
+                state.index % (nx * ny),
+                nx * ny,
+                dimension_hash(&state, 0) as u32,
+            );
+            (
+                ((cell % nx) as f64 + random_double()) / nx as f64,
+                ((cell / nx) as f64 + random_double()) / ny as f64,
+            )
+        }
+        SamplerKind::Halton => (halton(&state, 0), halton(&state, 1)),
+        SamplerKind::Sobol => sobol_2d(&state),
+    }
+}
+
+// Reserves `count` dimensions, returns the state with the first one
+fn next_dimensions(count: u64) -> SampleState {
+    STATE.with(|state| {
+        let current = state.get();
+        state.set(SampleState {
+            dimension: current.dimension + count,
+            ..current
+        });
+        current
+    })
+}
+
+fn dimension_hash(state: &SampleState, offset: u64) -> u64 {
+    sample_seed(state.seed, state.dimension + offset, 0)
+}
+
+fn halton(state: &SampleState, offset: u64) -> f64 {
+    let Some(&base) = PRIMES.get((state.dimension + offset) as usize) else {
+        return random_double();
+    };
+    // Cranley-Patterson rotation by a per pixel shift
+    let shift = (dimension_hash(state, offset) >> 11) as f64 / (1u64 << 53) as f64;
+    let value = radical_inverse(base, state.index) + shift;
+    value - f64::floor(value)
+}
+
+/// Mirrors the digits of `index` written in `base` around the radix point
+pub fn radical_inverse(base: u64, index: u64) -> f64 {
+    let inv_base = 1. / base as f64;
+    let mut inv_base_n = 1.;
+    let mut reversed = 0;
+    let mut index = index;
+    while index > 0 {
+        let next = index / base;
+        let digit = index - next * base;
+        reversed = reversed * base + digit;
+        inv_base_n *= inv_base;
+        index = next;
+    }
+    f64::min(reversed as f64 * inv_base_n, ONE_MINUS_EPSILON)
+}
+
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+// Burley, "Practical Hash-based Owen Scrambling" (2020). Every pair of dimensions uses the first
+// two dimensions of the Sobol sequence with its own scrambling, and the sample index is
+// shuffled per pair so that the pairs are not correlated with each other.
+fn sobol_2d(state: &SampleState) -> (f64, f64) {
+    let seed = dimension_hash(state, 0);
+    let index = nested_uniform_scramble(state.index as u32, seed as u32);
+    let (x, y) = sobol_2d_unscrambled(index);
+    let x = nested_uniform_scramble(x, (seed >> 32) as u32);
+    let y = nested_uniform_scramble(y, hash_u32((seed >> 32) as u32));
+    (to_unit(x), to_unit(y))
+}
+
+/// First two dimensions of the Sobol sequence as 32 bit fixed point numbers
+pub fn sobol_2d_unscrambled(index: u32) -> (u32, u32) {
+    // The first dimension is the van der Corput sequence, the second uses the direction numbers
+    // of the primitive polynomial x + 1
+    let x = index.reverse_bits();
+    let mut y = 0;
+    let mut v: u32 = 1 << 31;
+    let mut index = index;
+    while index != 0 {
+        if index & 1 != 0 {
+            y ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    (x, y)
+}
+
+fn laine_karras_permutation(x: u32, seed: u32) -> u32 {
+    let mut x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+// Owen scrambling: every bit is flipped depending on the bits above it
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+fn hash_u32(x: u32) -> u32 {
+    let mut x = x;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+fn to_unit(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+/// Element `i` of a random permutation of 0..n chosen by `seed`, without building the
+/// permutation (Kensler, "Correlated Multi-Jittered Sampling")
+pub fn permutation_element(i: u64, n: u64, seed: u32) -> u64 {
+    if n <= 1 {
+        return 0;
+    }
+    let (l, p) = (n as u32, seed);
+    let mut i = i as u32;
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p) % l) as u64
+}
+
+/// Maps the unit square to the unit disk, keeping neighbouring points together (Shirley and
+/// Chiu's concentric mapping)
+pub fn square_to_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2. * u - 1., 2. * v - 1.);
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4. * (b / a))
+    } else {
+        (b, PI / 2. - PI / 4. * (a / b))
+    };
+    (r * f64::cos(theta), r * f64::sin(theta))
+}
+
+/// Cosine weighted direction around +z
+pub fn square_to_cosine_hemisphere(u: f64, v: f64) -> (f64, f64, f64) {
+    let (x, y) = square_to_disk(u, v);
+    let z = f64::sqrt(f64::max(0., 1. - x * x - y * y));
+    (x, y, z)
+}
+
+/// Uniformly distributed direction
+pub fn square_to_sphere(u: f64, v: f64) -> (f64, f64, f64) {
+    let z = 1. - 2. * u;
+    let r = f64::sqrt(f64::max(0., 1. - z * z));
+    let phi = 2. * PI * v;
+    (r * f64::cos(phi), r * f64::sin(phi), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks that `n` 2D points fall in distinct cells of a `cells` by `cells` grid
+    fn assert_one_per_cell(points: &[(f64, f64)], cells: usize) {
+        let mut seen = vec![false; cells * cells];
+        for &(x, y) in points {
+            assert!((0. ..1.).contains(&x) && (0. ..1.).contains(&y));
+            let cell = (y * cells as f64) as usize * cells + (x * cells as f64) as usize;
+            assert!(!seen[cell], "two samples in cell {}", cell);
+            seen[cell] = true;
+        }
+    }
+
+    fn pixel_samples(kind: SamplerKind, spp: u64) -> Vec<(f64, f64)> {
+        (0..spp)
+            .map(|i| {
+                start_sample(kind, 3, 17, i, spp);
+                get_2d()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stratified_and_sobol_fill_every_cell() {
+        assert_one_per_cell(&pixel_samples(SamplerKind::Stratified, 16), 4);
+        assert_one_per_cell(&pixel_samples(SamplerKind::Sobol, 16), 4);
+    }
+
+    #[test]
+    fn low_discrepancy_sequences() {
+        let sobol: Vec<(u32, u32)> = (0..4).map(sobol_2d_unscrambled).collect();
+        assert_eq!(
+            sobol,
+            [
+                (0, 0),
+                (1 << 31, 1 << 31),
+                (1 << 30, 3 << 30),
+                (3 << 30, 1 << 30)
+            ]
+        );
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 5) - 7. / 9.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn permutation_is_a_bijection() {
+        let mut seen = [false; 10];
+        for i in 0..10 {
+            seen[permutation_element(i, 10, 1234) as usize] = true;
+        }
+        assert!(seen.iter().all(|s| *s));
+    }
+}
@@ -1,15 +1,20 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crate::{
+    aov::Aovs,
+    background::Background,
     bvh::BVHNode,
-    camera::Camera,
+    camera::{Camera, CameraBuilder, Projection},
     color::Color,
+    denoise::{denoise, DenoiseSettings},
     hittable::{Hittable, HittableList},
     material::{Dielectric, Lambertian, Material, Metal},
     ray::Point3,
+    sampler::SamplerKind,
     sphere::Sphere,
     texture::{CheckerTexture, ImageTexture, NoiseTexture},
-    utils::{random_double, random_double_range},
+    tile::{RenderControl, Tile, TileOrder},
+    utils::{random_double, random_double_range, seed_random, DEFAULT_SEED},
     vec3::Vec3,
 };
 use js_sys::{Uint8ClampedArray, WebAssembly};
@@ -22,6 +27,17 @@ struct CameraUpdate {
     aspect_ratio: Option<f64>,
     samples_per_pixel: Option<u32>,
     max_depth: Option<i32>,
+    max_diffuse_depth: Option<i32>,
+    max_specular_depth: Option<i32>,
+    max_transmission_depth: Option<i32>,
+    russian_roulette_depth: Option<i32>,
+    sampler: Option<SamplerKind>,
+    tile_size: Option<i32>,
+    tile_order: Option<TileOrder>,
+    spectral: Option<bool>,
+    shutter_open: Option<f64>,
+    shutter_close: Option<f64>,
+    projection: Option<Projection>,
     vfov: Option<f64>,
     lookfrom: Option<[f64; 3]>,
     lookat: Option<[f64; 3]>,
@@ -30,6 +46,14 @@ struct CameraUpdate {
     focus_dist: Option<f64>,
 }
 
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum BackgroundUpdate {
+    None,
+    Solid { color: [f64; 3] },
+    Gradient { top: [f64; 3], bottom: [f64; 3] },
+}
+
 #[derive(Serialize)]
 #[wasm_bindgen]
 pub struct Scene {
@@ -40,35 +64,55 @@ pub struct Scene {
     samples_per_pixel: u32,
     #[serde(skip)]
     world: Arc<dyn Hittable>,
+    #[serde(skip)]
+    lights: HittableList,
+    #[serde(skip)]
+    pending_tiles: Vec<Tile>, // tiles of the current sample pass that are not rendered yet
+    denoise: bool,
+    #[serde(skip)]
+    aovs: Option<Aovs>, // denoiser features, rendered when first needed after a camera change
 }
 
+// Camera samples per pixel of the AOVs guiding the denoiser
+const AOV_SAMPLES: u32 = 4;
+
 #[wasm_bindgen]
-pub fn hello() -> JsValue {
-    let lookfrom = Point3::new(13., 2., 3.);
-    let lookat = Point3::new(0., 0., 0.);
-    let vup = Vec3::new(0., 1., 0.);
-    let camera = Camera::new(44444, 1., 100, 50, 20., lookfrom, lookat, vup, 0., 12.);
-    serde_wasm_bindgen::to_value(&camera).unwrap()
+pub fn hello() -> Result<JsValue, JsValue> {
+    let camera = Camera::builder()
+        .aspect_ratio(1.)
+        .vfov(20.)
+        .lookfrom(Point3::new(13., 2., 3.))
+        .lookat(Point3::new(0., 0., 0.))
+        .focus_dist(12.)
+        .build()
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(serde_wasm_bindgen::to_value(&camera)?)
 }
 
 #[wasm_bindgen]
 impl Scene {
-    pub fn new(width: i32, aspect_ratio: f64, samples_per_pixel: i32, max_depth: i32) -> Self {
-        let lookfrom = Point3::new(13., 2., 3.);
-        let lookat = Point3::new(0., 0., 0.);
-        let vup = Vec3::new(0., 1., 0.);
-        let camera = Camera::new(
-            width,
-            aspect_ratio,
-            1, // Modification to do progressive rendering
-            max_depth,
-            20.,
-            lookfrom,
-            lookat,
-            vup,
-            0.,
-            12.,
-        );
+    pub fn new(
+        width: i32,
+        aspect_ratio: f64,
+        samples_per_pixel: i32,
+        max_depth: i32,
+    ) -> Result<Scene, JsValue> {
+        let camera = Camera::builder()
+            .image_width(width)
+            .aspect_ratio(aspect_ratio)
+            .samples_per_pixel(1) // Modification to do progressive rendering
+            // Stratified samplers spread their strata over all the passes
+            .total_samples(Some(samples_per_pixel))
+            .max_depth(max_depth)
+            .vfov(20.)
+            .lookfrom(Point3::new(13., 2., 3.))
+            .lookat(Point3::new(0., 0., 0.))
+            .focus_dist(12.)
+            .build()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        // Same random spheres on every page load
+        seed_random(DEFAULT_SEED);
         let mut world = HittableList::new();
 
         let checker = Arc::new(CheckerTexture::with_color(
@@ -120,14 +164,18 @@ impl Scene {
         world.add(Arc::new(Sphere::new(Point3::new(-4., 1., 0.), 1., mat3)));
         let bvh = BVHNode::new(&mut world) as Arc<dyn Hittable>;
 
-        Self {
+        Ok(Self {
             image: vec![255; 4 * camera.image_width() * camera.image_height()],
             buffer: vec![Color::default(); camera.image_width() * camera.image_height()],
             camera,
             current_sample_count: 0,
             samples_per_pixel: samples_per_pixel as u32,
             world: bvh,
-        }
+            lights: HittableList::new(),
+            pending_tiles: Vec::new(),
+            denoise: false,
+            aovs: None,
+        })
     }
 
     // Helps with debugging values
@@ -149,12 +197,103 @@ impl Scene {
 
     // Basically captures one new ray sample per pixel
     pub fn render(&mut self) {
+        self.render_pass(&RenderControl::new(), None);
+    }
+
+    /// Renders tiles of the current sample pass until `time_budget_ms` runs out, so that big
+    /// images do not block the page for a whole pass. `on_tile` is called with the
+    /// `{ x, y, width, height }` of every finished tile. Returns true once the pass is complete.
+    pub fn render_tiles(&mut self, time_budget_ms: f64, on_tile: Option<js_sys::Function>) -> bool {
+        let budget = Duration::from_secs_f64(f64::max(time_budget_ms, 0.) / 1000.);
+        self.render_pass(&RenderControl::with_time_budget(budget), on_tile)
+    }
+
+    /// Fraction of the current sample pass that is rendered
+    pub fn pass_progress(&self) -> f64 {
+        if self.pending_tiles.is_empty() {
+            return 1.;
+        }
+        let total = self.camera.tiles().len();
+        (total - self.pending_tiles.len()) as f64 / total as f64
+    }
+
+    fn render_pass(&mut self, control: &RenderControl, on_tile: Option<js_sys::Function>) -> bool {
+        if self.pending_tiles.is_empty() {
+            self.pending_tiles = self.camera.tiles();
+        }
+        let first_sample = self.current_sample_count as u64;
+        let sample_count = (self.current_sample_count + 1) as f64;
+        let image_width = self.camera.image_width();
+        let mut finished = Vec::new();
+        let buffer = &mut self.buffer;
+        let image = &mut self.image;
+        self.camera.render_tiles(
+            &self.world,
+            &self.lights,
+            &self.pending_tiles,
+            first_sample,
+            control,
+            |result| {
+                let tile = result.tile;
+                for (k, s) in result.pixels.into_iter().enumerate() {
+                    let x = tile.x as usize + k % tile.width as usize;
+                    let y = tile.y as usize + k / tile.width as usize;
+                    let i = y * image_width + x;
+                    buffer[i] += s;
+                    let rgb = (buffer[i] / sample_count).get_rgb();
+                    image[i * 4 + 0] = rgb[0];
+                    image[i * 4 + 1] = rgb[1];
+                    image[i * 4 + 2] = rgb[2];
+                }
+                finished.push(tile);
+                if let Some(on_tile) = &on_tile {
+                    let tile = serde_wasm_bindgen::to_value(&tile).unwrap();
+                    let _ = on_tile.call1(&JsValue::NULL, &tile);
+                }
+            },
+        );
+
+        self.pending_tiles.retain(|tile| !finished.contains(tile));
+        if !self.pending_tiles.is_empty() {
+            return false;
+        }
         self.current_sample_count += 1;
-        let frame_sample = self.camera.render(&self.world);
-        for (i, s) in frame_sample.into_iter().enumerate() {
-            self.buffer[i] += s;
-            let rgb = (self.buffer[i] / self.current_sample_count as f64).get_rgb();
-            self.image[i * 4 + 0] = rgb[0];
+        if self.denoise {
+            self.update_image();
+        }
+        true
+    }
+
+    /// Filters the preview with the denoiser after every sample pass
+    pub fn set_denoise(&mut self, enabled: bool) {
+        self.denoise = enabled;
+        // Halfway through a pass the pixels hold different numbers of samples, the image is
+        // brought up to date when the pass completes instead
+        if self.pending_tiles.is_empty() {
+            self.update_image();
+        }
+    }
+
+    // Rewrites the whole image from the accumulated samples
+    fn update_image(&mut self) {
+        if self.current_sample_count == 0 {
+            return;
+        }
+        let mut pixels: Vec<Color> = self
+            .buffer
+            .iter()
+            .map(|c| *c / self.current_sample_count as f64)
+            .collect();
+        if self.denoise {
+            let aovs = self.aovs.get_or_insert_with(|| {
+                self.camera
+                    .render_aovs_with_samples(&self.world, AOV_SAMPLES)
+            });
+            pixels = denoise(&pixels, aovs, &DenoiseSettings::default());
+        }
+        for (i, p) in pixels.into_iter().enumerate() {
+            let rgb = p.get_rgb();
+            self.image[i * 4] = rgb[0];
             self.image[i * 4 + 1] = rgb[1];
             self.image[i * 4 + 2] = rgb[2];
         }
@@ -170,6 +309,8 @@ impl Scene {
         self.current_sample_count
     }
     pub fn clear(&mut self) {
+        self.pending_tiles.clear();
+        self.aovs = None;
         self.buffer = vec![Color::default(); self.image_width() * self.image_height()];
         self.image = vec![255; 4 * self.image_width() * self.image_height()];
     }
@@ -192,22 +333,84 @@ impl Scene {
             .map(|arr| Vec3::new(arr[0], arr[1], arr[2]))
             .unwrap_or_else(|| self.camera.vup);
 
-        self.camera = Camera::new(
-            camera_update
-                .width
-                .unwrap_or(self.camera.image_width() as i32),
-            camera_update.aspect_ratio.unwrap_or(16. / 9.),
-            1, // Keep progressive rendering
-            camera_update.max_depth.unwrap_or(self.camera.max_depth),
-            camera_update.vfov.unwrap_or(self.camera.vfov),
-            lookfrom,
-            lookat,
-            vup,
-            camera_update
-                .defocus_angle
-                .unwrap_or(self.camera.defocus_angle),
-            camera_update.focus_dist.unwrap_or(self.camera.focus_dist),
-        );
+        let mut bounce_limits = self.camera.bounce_limits;
+        bounce_limits.diffuse = camera_update
+            .max_diffuse_depth
+            .unwrap_or(bounce_limits.diffuse);
+        bounce_limits.specular = camera_update
+            .max_specular_depth
+            .unwrap_or(bounce_limits.specular);
+        bounce_limits.transmission = camera_update
+            .max_transmission_depth
+            .unwrap_or(bounce_limits.transmission);
+
+        let samples_per_pixel = camera_update
+            .samples_per_pixel
+            .unwrap_or(self.samples_per_pixel);
+
+        // Everything not in the update, background and seed included, stays as it was
+        let mut builder = CameraBuilder::from_camera(&self.camera);
+        if camera_update.width.is_some() || camera_update.aspect_ratio.is_some() {
+            let aspect_ratio = self.camera.image_width as f64 / self.camera.image_height as f64;
+            builder = builder
+                .image_width(camera_update.width.unwrap_or(self.camera.image_width))
+                .aspect_ratio(camera_update.aspect_ratio.unwrap_or(aspect_ratio));
+        }
+        self.camera = builder
+            .samples_per_pixel(1) // Keep progressive rendering
+            .total_samples(Some(samples_per_pixel as i32))
+            .max_depth(camera_update.max_depth.unwrap_or(self.camera.max_depth))
+            .bounce_limits(bounce_limits)
+            .russian_roulette_depth(
+                camera_update
+                    .russian_roulette_depth
+                    .unwrap_or(self.camera.russian_roulette_depth),
+            )
+            .sampler(camera_update.sampler.unwrap_or(self.camera.sampler))
+            .tile_size(camera_update.tile_size.unwrap_or(self.camera.tile_size))
+            .tile_order(camera_update.tile_order.unwrap_or(self.camera.tile_order))
+            .spectral(camera_update.spectral.unwrap_or(self.camera.spectral))
+            .shutter(
+                camera_update
+                    .shutter_open
+                    .unwrap_or(self.camera.shutter_open),
+                camera_update
+                    .shutter_close
+                    .unwrap_or(self.camera.shutter_close),
+            )
+            .projection(camera_update.projection.unwrap_or(self.camera.projection))
+            .vfov(camera_update.vfov.unwrap_or(self.camera.vfov))
+            .lookfrom(lookfrom)
+            .lookat(lookat)
+            .vup(vup)
+            .defocus_angle(
+                camera_update
+                    .defocus_angle
+                    .unwrap_or(self.camera.defocus_angle),
+            )
+            .focus_dist(camera_update.focus_dist.unwrap_or(self.camera.focus_dist))
+            .build()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.samples_per_pixel = samples_per_pixel;
+
+        self.clear();
+        self.current_sample_count = 0;
+
+        Ok(())
+    }
+
+    pub fn set_background(&mut self, js_background: JsValue) -> Result<(), JsValue> {
+        let background_update: BackgroundUpdate = serde_wasm_bindgen::from_value(js_background)?;
+        let to_color = |arr: [f64; 3]| Color::new(arr[0], arr[1], arr[2]);
+
+        self.camera.background = match background_update {
+            BackgroundUpdate::None => Background::None,
+            BackgroundUpdate::Solid { color } => Background::Solid(to_color(color)),
+            BackgroundUpdate::Gradient { top, bottom } => Background::Gradient {
+                top: to_color(top),
+                bottom: to_color(bottom),
+            },
+        };
 
         self.clear();
         self.current_sample_count = 0;
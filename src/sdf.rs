@@ -0,0 +1,242 @@
+use std::{f64::consts::PI, fmt, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::{Point3, Ray},
+    vec3::{unit_vector, Vec3},
+};
+
+/// Signed distance function: negative inside the shape, positive outside, and never larger than
+/// the true distance to the surface
+pub type DistanceFn = Arc<dyn Fn(&Point3) -> f64 + Send + Sync>;
+
+/// A shape defined by a distance function, rendered by sphere tracing inside its bounding box
+pub struct Sdf {
+    distance: DistanceFn,
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+    max_steps: u32,
+    epsilon: f64,
+}
+
+impl fmt::Debug for Sdf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sdf")
+            .field("material", &self.material)
+            .field("bbox", &self.bbox)
+            .field("max_steps", &self.max_steps)
+            .field("epsilon", &self.epsilon)
+            .finish()
+    }
+}
+
+impl Sdf {
+    /// `bbox` must enclose the whole surface, rays are only marched inside it
+    pub fn new(distance: DistanceFn, bbox: AABB, material: Arc<dyn Material>) -> Self {
+        Self {
+            distance,
+            material: Some(material),
+            bbox,
+            max_steps: 256,
+            epsilon: 1e-4,
+        }
+    }
+
+    /// Fractals and other distance bounds that are not exact need more, smaller steps
+    pub fn with_precision(mut self, max_steps: u32, epsilon: f64) -> Self {
+        self.max_steps = max_steps;
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// Surface normal from the gradient of the distance field, estimated with central differences
+    fn normal(&self, p: &Point3) -> Vec3 {
+        let h = self.epsilon;
+        let d = &self.distance;
+        let dx = Vec3::new(h, 0., 0.);
+        let dy = Vec3::new(0., h, 0.);
+        let dz = Vec3::new(0., 0., h);
+        unit_vector(&Vec3::new(
+            d(&(*p + dx)) - d(&(*p - dx)),
+            d(&(*p + dy)) - d(&(*p - dy)),
+            d(&(*p + dz)) - d(&(*p - dz)),
+        ))
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        let Some(box_t) = self.bbox.hit_interval(r, ray_t) else {
+            return false;
+        };
+
+        // March in units of world distance, the ray direction is not necessarily normalized
+        let ray_length = r.direction().length();
+        let mut t = box_t.min;
+
+        // When the ray starts inside the shape (e.g. a refracted ray), march towards the exit
+        let sign = if (self.distance)(&r.at(t)) < 0. {
+            -1.
+        } else {
+            1.
+        };
+
+        for _ in 0..self.max_steps {
+            let p = r.at(t);
+            let d = sign * (self.distance)(&p);
+            if d < self.epsilon {
+                // Skip the surface the ray is leaving from, e.g. the starting point of a
+                // scattered ray
+                if t > ray_t.min {
+                    let outward_normal = self.normal(&p);
+                    rec.t = t;
+                    rec.p = p;
+                    rec.material = self.material.clone();
+                    rec.set_face_normal(r, &outward_normal);
+                    // Spherical mapping of the normal, the same way as Sphere
+                    rec.u = (f64::atan2(-outward_normal.z(), outward_normal.x()) + PI) / (2. * PI);
+                    rec.v = f64::acos(-outward_normal.y()) / PI;
+                    return true;
+                }
+                t += 2. * self.epsilon / ray_length;
+                continue;
+            }
+            t += d / ray_length;
+            if t > box_t.max {
+                return false;
+            }
+        }
+        false
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+// Primitive distance functions, all centered at the origin. Move them around with `translate`
+// or wrap the resulting Sdf in a `transform::Transform`.
+
+pub fn sphere(radius: f64) -> DistanceFn {
+    Arc::new(move |p| p.length() - radius)
+}
+
+/// Box with the given half extents, whose edges are rounded by `radius`
+pub fn round_box(half_extents: Vec3, radius: f64) -> DistanceFn {
+    Arc::new(move |p| {
+        let q = Vec3::new(
+            f64::abs(p.x()) - half_extents.x() + radius,
+            f64::abs(p.y()) - half_extents.y() + radius,
+            f64::abs(p.z()) - half_extents.z() + radius,
+        );
+        let outside = Vec3::new(
+            f64::max(q.x(), 0.),
+            f64::max(q.y(), 0.),
+            f64::max(q.z(), 0.),
+        );
+        outside.length() + f64::min(f64::max(q.x(), f64::max(q.y(), q.z())), 0.) - radius
+    })
+}
+
+/// Torus lying in the xz plane
+pub fn torus(major_radius: f64, minor_radius: f64) -> DistanceFn {
+    Arc::new(move |p| {
+        let ring = f64::sqrt(p.x() * p.x() + p.z() * p.z()) - major_radius;
+        f64::sqrt(ring * ring + p.y() * p.y()) - minor_radius
+    })
+}
+
+/// Distance estimate of the power-8 Mandelbulb, which fits inside a sphere of radius ~1.2
+pub fn mandelbulb(power: f64, iterations: u32) -> DistanceFn {
+    Arc::new(move |p| {
+        let mut z = *p;
+        let mut dr = 1.;
+        let mut r = 0.;
+        for _ in 0..iterations {
+            r = z.length();
+            if r > 2. {
+                break;
+            }
+            // Convert to polar coordinates, scale and rotate the point
+            let theta = f64::acos(z.z() / r) * power;
+            let phi = f64::atan2(z.y(), z.x()) * power;
+            dr = f64::powf(r, power - 1.) * power * dr + 1.;
+            let zr = f64::powf(r, power);
+            z = Vec3::new(
+                f64::sin(theta) * f64::cos(phi),
+                f64::sin(phi) * f64::sin(theta),
+                f64::cos(theta),
+            ) * zr
+                + *p;
+        }
+        if r < 1e-12 {
+            return -1.;
+        }
+        0.5 * f64::ln(r) * r / dr
+    })
+}
+
+// Composition operators
+
+pub fn translate(a: DistanceFn, offset: Vec3) -> DistanceFn {
+    Arc::new(move |p| a(&(*p - offset)))
+}
+
+pub fn union(a: DistanceFn, b: DistanceFn) -> DistanceFn {
+    Arc::new(move |p| f64::min(a(p), b(p)))
+}
+
+pub fn intersection(a: DistanceFn, b: DistanceFn) -> DistanceFn {
+    Arc::new(move |p| f64::max(a(p), b(p)))
+}
+
+/// Removes `b` from `a`
+pub fn subtraction(a: DistanceFn, b: DistanceFn) -> DistanceFn {
+    Arc::new(move |p| f64::max(a(p), -b(p)))
+}
+
+/// Union that blends the two shapes together over a distance of about `k`
+pub fn smooth_union(a: DistanceFn, b: DistanceFn, k: f64) -> DistanceFn {
+    Arc::new(move |p| {
+        let (da, db) = (a(p), b(p));
+        let h = f64::clamp(0.5 + 0.5 * (db - da) / k, 0., 1.);
+        db * (1. - h) + da * h - k * h * (1. - h)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian};
+
+    #[test]
+    fn sphere_trace_matches_analytic_sphere() {
+        let shape = Sdf::new(
+            translate(sphere(1.), Vec3::new(0., 0., -5.)),
+            AABB::with_points(&Point3::new(-1., -1., -6.), &Point3::new(1., 1., -4.)),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        let r = Ray::new(Point3::new(0., 0., 0.), Vec3::new(0., 0., -2.));
+        let mut rec = HitRecord::default();
+        assert!(shape.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 2.).abs() < 1e-3);
+        assert!((rec.normal - Vec3::new(0., 0., 1.)).length() < 1e-3);
+        assert!(rec.front_face);
+    }
+
+    #[test]
+    fn subtraction_carves_hole() {
+        let d = subtraction(sphere(1.), sphere(0.5));
+        assert!(d(&Point3::new(0., 0., 0.)) > 0.);
+        assert!(d(&Point3::new(0.75, 0., 0.)) < 0.);
+        let s = smooth_union(
+            sphere(1.),
+            translate(sphere(1.), Vec3::new(1.5, 0., 0.)),
+            1.,
+        );
+        assert!(s(&Point3::new(0.75, 0.9, 0.)) < 0.);
+    }
+}
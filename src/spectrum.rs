@@ -0,0 +1,220 @@
+// Conversions for spectral rendering. Every path carries a hero wavelength (in nanometres) and
+// two companions spread evenly over the visible range, one per channel of a Color. RGB colors of
+// materials, lights and backgrounds are upsampled to a spectrum and evaluated at those
+// wavelengths, and the radiance found by the path is turned back into RGB through the CIE color
+// matching functions. Materials that bend each wavelength differently can only follow the hero.
+
+use std::sync::OnceLock;
+
+use crate::{color::Color, vec3::Vec3};
+
+// Range of the wavelengths sampled by the camera
+pub const MIN_WAVELENGTH: f64 = 360.;
+pub const MAX_WAVELENGTH: f64 = 830.;
+
+/// Picks a wavelength from `u` in [0,1), favouring the ones the eye is most sensitive to (pbrt's
+/// visible wavelength distribution). Returns the wavelength and its density.
+pub fn sample_visible_wavelength(u: f64) -> (f64, f64) {
+    let wavelength = 538. - 138.888889 * f64::atanh(0.85691062 - 1.82750197 * u);
+    let wavelength = f64::clamp(wavelength, MIN_WAVELENGTH, MAX_WAVELENGTH);
+    (wavelength, visible_wavelength_pdf(wavelength))
+}
+
+// Inverse of sample_visible_wavelength
+fn visible_wavelength_cdf(wavelength: f64) -> f64 {
+    (0.85691062 - f64::tanh((538. - wavelength) / 138.888889)) / 1.82750197
+}
+
+/// The hero wavelength and its two companions, a third of the distribution further each. All
+/// zero for a hero of 0, which stands for RGB rendering.
+pub fn hero_wavelengths(hero: f64) -> Vec3 {
+    if hero <= 0. {
+        return Vec3::default();
+    }
+    let u = visible_wavelength_cdf(hero);
+    let companion = |offset: f64| {
+        let v = u + offset;
+        sample_visible_wavelength(v - f64::floor(v)).0
+    };
+    Vec3::new(hero, companion(1. / 3.), companion(2. / 3.))
+}
+
+pub fn visible_wavelength_pdf(wavelength: f64) -> f64 {
+    if !(MIN_WAVELENGTH..=MAX_WAVELENGTH).contains(&wavelength) {
+        return 0.;
+    }
+    0.0039398042 / f64::cosh(0.0072 * (wavelength - 538.)).powi(2)
+}
+
+/// CIE 1931 color matching functions, from the multi-lobe fit of Wyman, Sloan and Shirley,
+/// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions" (2013)
+pub fn cie_xyz(wavelength: f64) -> Vec3 {
+    // Gaussian with a different width on each side of the peak
+    let g = |mu: f64, sigma_left: f64, sigma_right: f64| {
+        let sigma = if wavelength < mu {
+            sigma_left
+        } else {
+            sigma_right
+        };
+        let t = (wavelength - mu) / sigma;
+        f64::exp(-0.5 * t * t)
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// CIE XYZ to linear sRGB (D65)
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// RGB of a constant spectrum of 1, used to white balance so that gray stays gray
+fn white_rgb() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+        let xyz = (0..steps).fold(Vec3::default(), |sum, i| {
+            sum + cie_xyz(MIN_WAVELENGTH + i as f64 + 0.5)
+        });
+        xyz_to_rgb(xyz)
+    })
+}
+
+/// Contribution to the RGB value of a pixel of a path with the given hero wavelength, which
+/// found `radiance` at the hero and companion wavelengths. Averaging these over hero wavelengths
+/// sampled with `sample_visible_wavelength` converges to the color of the spectrum.
+pub fn wavelength_sample_to_rgb(radiance: Color, hero: f64) -> Color {
+    let wavelengths = hero_wavelengths(hero);
+    let white = white_rgb();
+    (0..3).fold(Color::default(), |sum, i| {
+        let pdf = visible_wavelength_pdf(wavelengths[i]);
+        if pdf <= 0. {
+            return sum;
+        }
+        let rgb = xyz_to_rgb(cie_xyz(wavelengths[i]) * (radiance[i] / (3. * pdf)));
+        sum + Color::new(
+            rgb.x() / white.x(),
+            rgb.y() / white.y(),
+            rgb.z() / white.z(),
+        )
+    })
+}
+
+/// `color` as seen by a path carrying `wavelengths` (see `hero_wavelengths`): its upsampled
+/// spectrum at each of them. Zero wavelengths mean RGB rendering and keep the color as it is.
+pub fn at_wavelengths(color: Color, wavelengths: Vec3) -> Color {
+    if wavelengths.x() <= 0. {
+        return color;
+    }
+    Color::new(
+        rgb_to_spectrum(color, wavelengths.x()),
+        rgb_to_spectrum(color, wavelengths.y()),
+        rgb_to_spectrum(color, wavelengths.z()),
+    )
+}
+
+// Smits, "An RGB to Spectrum Conversion for Reflectances" (1999): the spectrum is built from
+// white and the smoothest spectra for the primaries and their complements, given in 10 bins
+// between 380 and 720 nm
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// Value at `wavelength` of a smooth spectrum with the color `rgb`
+pub fn rgb_to_spectrum(rgb: Color, wavelength: f64) -> f64 {
+    let basis = |table: &[f64; 10]| {
+        // Linear interpolation between the bin centres
+        let x = (wavelength - 380.) / 34. - 0.5;
+        let i = f64::clamp(x.floor(), 0., 8.) as usize;
+        let t = f64::clamp(x - i as f64, 0., 1.);
+        table[i] * (1. - t) + table[i + 1] * t
+    };
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    let white = basis(&SMITS_WHITE);
+    if r <= g && r <= b {
+        let base = r * white + basis(&SMITS_CYAN) * (f64::min(g, b) - r);
+        if g <= b {
+            base + basis(&SMITS_BLUE) * (b - g)
+        } else {
+            base + basis(&SMITS_GREEN) * (g - b)
+        }
+    } else if g <= r && g <= b {
+        let base = g * white + basis(&SMITS_MAGENTA) * (f64::min(r, b) - g);
+        if r <= b {
+            base + basis(&SMITS_BLUE) * (b - r)
+        } else {
+            base + basis(&SMITS_RED) * (r - b)
+        }
+    } else {
+        let base = b * white + basis(&SMITS_YELLOW) * (f64::min(r, g) - b);
+        if r <= g {
+            base + basis(&SMITS_GREEN) * (g - r)
+        } else {
+            base + basis(&SMITS_RED) * (r - g)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Color of the upsampled spectrum of `rgb`, integrated with many wavelength samples
+    fn round_trip(rgb: Color) -> Color {
+        let n = 20000;
+        (0..n).fold(Color::default(), |sum, i| {
+            let (hero, _) = sample_visible_wavelength((i as f64 + 0.5) / n as f64);
+            sum + wavelength_sample_to_rgb(at_wavelengths(rgb, hero_wavelengths(hero)), hero)
+        }) / n as f64
+    }
+
+    #[test]
+    fn companions_follow_the_hero() {
+        let (hero, _) = sample_visible_wavelength(0.9);
+        let wavelengths = hero_wavelengths(hero);
+        assert_eq!(wavelengths.x(), hero);
+        assert!((visible_wavelength_cdf(wavelengths.y()) - (0.9 + 1. / 3. - 1.)).abs() < 1e-6);
+        assert!((visible_wavelength_cdf(wavelengths.z()) - (0.9 + 2. / 3. - 1.)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn colors_survive_the_round_trip() {
+        let white = round_trip(Color::new(1., 1., 1.));
+        assert!(
+            (white - Color::new(1., 1., 1.)).length() < 0.01,
+            "{}",
+            white
+        );
+        let orange = round_trip(Color::new(0.8, 0.4, 0.1));
+        assert!(
+            (orange - Color::new(0.8, 0.4, 0.1)).length() < 0.1,
+            "{}",
+            orange
+        );
+    }
+}
@@ -3,7 +3,9 @@ use crate::{
     hittable::{HitRecord, Hittable},
     interval::Interval,
     material::Material,
+    onb::ONB,
     ray::{Point3, Ray},
+    utils::random_double,
     vec3::{dot, Vec3},
 };
 use std::{f64::consts::PI, sync::Arc};
@@ -57,10 +59,9 @@ impl Sphere {
         *u = phi / (2. * PI);
         *v = theta / PI;
     }
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    // Both roots of the ray-sphere quadratic (ordered), if the ray hits the sphere at all
+    fn roots(&self, r: &Ray) -> Option<(f64, f64)> {
         let current_center = self.center.at(r.time());
         let oc = current_center - r.origin(); // C - Q
         let a = r.direction().length_squared(); // d * d
@@ -68,35 +69,108 @@ impl Hittable for Sphere {
         let c = oc.length_squared() - self.radius * self.radius; // (C-Q)*(C-Q) - radius^2
         let discriminant = h * h - a * c;
         if discriminant < 0. {
-            return false;
+            return None;
         }
-
-        // Here we are computing the full quadratic equation
-        // We are checking if the resulting 't' falls inside the accepted interval
         let sqrtd = f64::sqrt(discriminant);
+        Some(((h - sqrtd) / a, (h + sqrtd) / a))
+    }
 
-        // Check if root falls in acceptable range. Check for both signs of the root
-        let mut root = (h - sqrtd) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
-            if !ray_t.surrounds(root) {
-                return false;
-            }
-        }
+    fn set_hit_record(&self, r: &Ray, root: f64, rec: &mut HitRecord) {
         // We update the hitrecord with the 't', point of intersect
         // and the unit-length of the intersect surface normal
+        let current_center = self.center.at(r.time());
         rec.t = root;
         rec.p = r.at(rec.t);
         rec.material = self.material.clone();
         let outward_normal = (rec.p - current_center) / self.radius;
         rec.set_face_normal(r, &outward_normal);
         Self::get_sphere(&outward_normal, &mut rec.u, &mut rec.v);
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Here we are computing the full quadratic equation
+        // We are checking if the resulting 't' falls inside the accepted interval
+        let Some((near, far)) = self.roots(r) else {
+            return false;
+        };
+
+        // Check if root falls in acceptable range. Check for both signs of the root
+        let mut root = near;
+        if !ray_t.surrounds(root) {
+            root = far;
+            if !ray_t.surrounds(root) {
+                return false;
+            }
+        }
+        self.set_hit_record(r, root, rec);
         return true;
     }
 
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        let Some((near, far)) = self.roots(r) else {
+            return Vec::new();
+        };
+        [near, far]
+            .into_iter()
+            .filter(|root| ray_t.surrounds(*root))
+            .map(|root| {
+                let mut rec = HitRecord::default();
+                self.set_hit_record(r, root, &mut rec);
+                rec
+            })
+            .collect()
+    }
+
     fn bounding_box(&self) -> AABB {
         self.bbox
     }
+
+    // The sphere is sampled uniformly over the cone of directions it covers as seen from the
+    // origin. Moving spheres are sampled at their starting position.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let mut rec = HitRecord::default();
+        if !self.hit(
+            &Ray::new(*origin, *direction),
+            Interval::new(0.001, f64::INFINITY),
+            &mut rec,
+        ) {
+            return 0.;
+        }
+
+        let distance_squared = (self.center.at(0.) - *origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            // From inside, every direction hits the sphere
+            return 1. / (4. * PI);
+        }
+        let cos_theta_max = f64::sqrt(1. - self.radius * self.radius / distance_squared);
+        let solid_angle = 2. * PI * (1. - cos_theta_max);
+        1. / solid_angle
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let direction = self.center.at(0.) - *origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return Vec3::random_unit_vector();
+        }
+        let uvw = ONB::new(&direction);
+        uvw.transform(&random_to_sphere(self.radius, distance_squared))
+    }
+}
+
+// Random direction around +z inside the cone subtended by a sphere of the given radius at the
+// given squared distance
+fn random_to_sphere(radius: f64, distance_squared: f64) -> Vec3 {
+    let r1 = random_double();
+    let r2 = random_double();
+    let z = 1. + r2 * (f64::sqrt(1. - radius * radius / distance_squared) - 1.);
+
+    let phi = 2. * PI * r1;
+    let x = f64::cos(phi) * f64::sqrt(1. - z * z);
+    let y = f64::sin(phi) * f64::sqrt(1. - z * z);
+    Vec3::new(x, y, z)
 }
 
 pub fn hit_sphere_naive(center: &Point3, radius: f64, r: &Ray) -> f64 {
@@ -0,0 +1,25 @@
+//! Fixtures shared by the tests of the primitives
+
+use std::sync::Arc;
+
+use crate::{
+    color::Color,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::{Lambertian, Material},
+    ray::Ray,
+};
+
+/// Plain grey diffuse material, for tests that only look at the geometry
+pub fn grey() -> Arc<dyn Material> {
+    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+}
+
+/// Closest hit of `ray` on `object` over the interval the renderer uses, which skips hits
+/// right at the ray origin
+pub fn hit(object: &dyn Hittable, ray: &Ray) -> Option<HitRecord> {
+    let mut rec = HitRecord::default();
+    object
+        .hit(ray, Interval::new(0.001, f64::INFINITY), &mut rec)
+        .then_some(rec)
+}
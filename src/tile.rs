@@ -0,0 +1,163 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{color::Color, utils::now_ms};
+
+/// Rectangle of pixels rendered as one unit of work
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Tile {
+    /// Copies per pixel `values` of the tile, row by row, into an image `image_width` wide
+    pub fn copy_into<T: Copy>(&self, values: &[T], image: &mut [T], image_width: i32) {
+        for (row, values) in values.chunks(self.width as usize).enumerate() {
+            let start = (self.y as usize + row) * image_width as usize + self.x as usize;
+            image[start..start + values.len()].copy_from_slice(values);
+        }
+    }
+}
+
+/// Order in which tiles are handed out to the render threads
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    #[default]
+    Scanline, // rows of tiles from the top left
+    Spiral, // outwards from the centre of the image, where the subject usually is
+}
+
+/// Splits the image into tiles of at most `tile_size` by `tile_size` pixels
+pub fn make_tiles(
+    image_width: i32,
+    image_height: i32,
+    tile_size: i32,
+    order: TileOrder,
+) -> Vec<Tile> {
+    let tile_size = i32::max(tile_size, 1);
+    let nx = (image_width + tile_size - 1) / tile_size;
+    let ny = (image_height + tile_size - 1) / tile_size;
+    let tile = |tx: i32, ty: i32| Tile {
+        x: tx * tile_size,
+        y: ty * tile_size,
+        width: i32::min(tile_size, image_width - tx * tile_size),
+        height: i32::min(tile_size, image_height - ty * tile_size),
+    };
+
+    match order {
+        TileOrder::Scanline => (0..ny)
+            .flat_map(|ty| (0..nx).map(move |tx| (tx, ty)))
+            .map(|(tx, ty)| tile(tx, ty))
+            .collect(),
+        TileOrder::Spiral => {
+            // Walk a square spiral (1 right, 1 down, 2 left, 2 up, 3 right, ...) from the
+            // centre tile, keeping the steps that land inside the grid
+            let count = (nx * ny) as usize;
+            let mut tiles = Vec::with_capacity(count);
+            let (mut tx, mut ty) = ((nx - 1) / 2, (ny - 1) / 2);
+            let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+            let mut leg = 0;
+            while tiles.len() < count {
+                let (dx, dy) = directions[leg % 4];
+                for _ in 0..leg / 2 + 1 {
+                    if (0..nx).contains(&tx) && (0..ny).contains(&ty) {
+                        tiles.push(tile(tx, ty));
+                    }
+                    tx += dx;
+                    ty += dy;
+                }
+                leg += 1;
+            }
+            tiles
+        }
+    }
+}
+
+/// Stops a tiled render early, either from another thread or after a time budget. Tiles that
+/// were already started are finished, the remaining ones are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct RenderControl {
+    cancelled: Arc<AtomicBool>,
+    deadline_ms: Option<f64>,
+}
+
+impl RenderControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops starting new tiles once `budget` has passed from now
+    pub fn with_time_budget(budget: Duration) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline_ms: Some(now_ms() + budget.as_secs_f64() * 1000.),
+        }
+    }
+
+    /// Can be called from any thread, clones share the flag
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_out_of_time(&self) -> bool {
+        self.deadline_ms
+            .is_some_and(|deadline| now_ms() >= deadline)
+    }
+
+    pub(crate) fn should_stop(&self) -> bool {
+        self.is_cancelled() || self.is_out_of_time()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStatus {
+    Finished,
+    Cancelled,
+    OutOfTime,
+}
+
+/// A finished tile, pixels are stored row by row
+#[derive(Debug, Clone)]
+pub struct TileResult {
+    pub tile: Tile,
+    pub pixels: Vec<Color>,
+    pub sample_counts: Vec<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_the_image_once() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral] {
+            let tiles = make_tiles(100, 70, 16, order);
+            let mut covered = vec![0; 100 * 70];
+            for tile in &tiles {
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        covered[(y * 100 + x) as usize] += 1;
+                    }
+                }
+            }
+            assert!(covered.iter().all(|&c| c == 1));
+        }
+        // The spiral starts in the middle
+        let spiral = make_tiles(100, 70, 16, TileOrder::Spiral);
+        assert_eq!((spiral[0].x, spiral[0].y), (48, 32));
+    }
+}
@@ -0,0 +1,140 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    numeric::solve_quartic,
+    ray::{Point3, Ray},
+    vec3::{dot, unit_vector, Vec3},
+};
+
+/// Torus lying in the xz plane around `center`. Other orientations can be obtained with a
+/// `transform::Transform`.
+#[derive(Debug)]
+pub struct Torus {
+    center: Point3,
+    major_radius: f64, // distance from the center to the middle of the tube
+    minor_radius: f64, // radius of the tube
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let major_radius = f64::max(0., major_radius);
+        let minor_radius = f64::max(0., minor_radius);
+        let extent = Vec3::new(
+            major_radius + minor_radius,
+            minor_radius,
+            major_radius + minor_radius,
+        );
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+            material: Some(material),
+            bbox: AABB::with_points(&(center - extent), &(center + extent)),
+        }
+    }
+
+    /// u: angle around the y axis, v: angle around the tube, both mapped to [0,1]
+    fn get_torus_uv(&self, p: &Point3) -> (f64, f64) {
+        let ring = f64::sqrt(p.x() * p.x() + p.z() * p.z()) - self.major_radius;
+        let u = (f64::atan2(-p.z(), p.x()) + PI) / (2. * PI);
+        let v = (f64::atan2(p.y(), ring) + PI) / (2. * PI);
+        (u, v)
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Skip rays that miss the bounding box, and start the others from where they enter it.
+        // Keeping the origin close to the torus keeps the quartic well conditioned.
+        let Some(box_t) = self.bbox.hit_interval(r, ray_t) else {
+            return false;
+        };
+        let t_offset = box_t.min;
+
+        // Torus at the origin: (|p|^2 + R^2 - r^2)^2 = 4R^2(x^2 + z^2), with p = o + td
+        let o = r.at(t_offset) - self.center;
+        let d = r.direction();
+        let four_r2 = 4. * self.major_radius * self.major_radius;
+        let dd = dot(d, d);
+        let f = dot(o, d);
+        let g = dot(o, o) + self.major_radius * self.major_radius
+            - self.minor_radius * self.minor_radius;
+
+        let roots = solve_quartic(
+            dd * dd,
+            4. * dd * f,
+            4. * f * f + 2. * dd * g - four_r2 * (d.x() * d.x() + d.z() * d.z()),
+            4. * f * g - 2. * four_r2 * (o.x() * d.x() + o.z() * d.z()),
+            g * g - four_r2 * (o.x() * o.x() + o.z() * o.z()),
+        );
+
+        let Some(root) = roots
+            .into_iter()
+            .map(|t| t + t_offset)
+            .find(|t| ray_t.surrounds(*t))
+        else {
+            return false;
+        };
+
+        rec.t = root;
+        rec.p = r.at(root);
+        rec.material = self.material.clone();
+
+        // The normal points away from the closest point on the ring running through the tube
+        let local = rec.p - self.center;
+        let radial = Vec3::new(local.x(), 0., local.z());
+        let ring_point = if radial.near_zero() {
+            Vec3::default()
+        } else {
+            unit_vector(&radial) * self.major_radius
+        };
+        let outward_normal = unit_vector(&(local - ring_point));
+        rec.set_face_normal(r, &outward_normal);
+        (rec.u, rec.v) = self.get_torus_uv(&local);
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian};
+
+    #[test]
+    fn hits_outer_and_inner_tube() {
+        let torus = Torus::new(
+            Point3::new(0., 0., 0.),
+            2.,
+            0.5,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        // Straight through the hole: the ray crosses the tube twice on each side
+        let r = Ray::new(Point3::new(-10., 0., 0.), Vec3::new(1., 0., 0.));
+        let mut rec = HitRecord::default();
+        assert!(torus.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 7.5).abs() < 1e-9);
+        assert!((rec.normal - Vec3::new(-1., 0., 0.)).length() < 1e-9);
+
+        assert!(torus.hit(&r, Interval::new(7.6, f64::INFINITY), &mut rec));
+        assert!((rec.t - 8.5).abs() < 1e-9);
+
+        // Down the middle of the hole misses
+        let r = Ray::new(Point3::new(0., 10., 0.), Vec3::new(0., -1., 0.));
+        assert!(!torus.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+    }
+}
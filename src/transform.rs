@@ -0,0 +1,354 @@
+use std::{ops::Mul, sync::Arc};
+
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    ray::{Point3, Ray},
+    utils::degrees_to_radians,
+    vec3::{unit_vector, Vec3},
+};
+
+/// An affine map `p -> m * p + t`, stored as a row-major 3x3 linear part and a translation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    m: [[f64; 3]; 3],
+    t: Vec3,
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Affine {
+    pub fn new(m: [[f64; 3]; 3], t: Vec3) -> Self {
+        Self { m, t }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]], Vec3::default())
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Self::new(Self::identity().m, offset)
+    }
+
+    pub fn scale(factors: Vec3) -> Self {
+        Self::new(
+            [
+                [factors.x(), 0., 0.],
+                [0., factors.y(), 0.],
+                [0., 0., factors.z()],
+            ],
+            Vec3::default(),
+        )
+    }
+
+    /// Counter-clockwise rotation of `degrees` around `axis` (Rodrigues' rotation formula)
+    pub fn rotation(axis: Vec3, degrees: f64) -> Self {
+        let a = unit_vector(&axis);
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let radians = degrees_to_radians(degrees);
+        let (sin_theta, cos_theta) = (f64::sin(radians), f64::cos(radians));
+        let k = 1. - cos_theta;
+        Self::new(
+            [
+                [
+                    cos_theta + x * x * k,
+                    x * y * k - z * sin_theta,
+                    x * z * k + y * sin_theta,
+                ],
+                [
+                    y * x * k + z * sin_theta,
+                    cos_theta + y * y * k,
+                    y * z * k - x * sin_theta,
+                ],
+                [
+                    z * x * k - y * sin_theta,
+                    z * y * k + x * sin_theta,
+                    cos_theta + z * z * k,
+                ],
+            ],
+            Vec3::default(),
+        )
+    }
+
+    /// Returns the transform that applies `self` first and then `next`
+    pub fn then(&self, next: &Affine) -> Self {
+        *next * *self
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        self.transform_vector(p) + self.t
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+            m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+            m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+        )
+    }
+
+    /// Multiplies by the transpose of the linear part. Applied on an inverse transform, this is
+    /// how normals are carried over by the forward transform.
+    pub fn transform_vector_transposed(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v[0] + m[1][0] * v[1] + m[2][0] * v[2],
+            m[0][1] * v[0] + m[1][1] * v[1] + m[2][1] * v[2],
+            m[0][2] * v[0] + m[1][2] * v[1] + m[2][2] * v[2],
+        )
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns None if the linear part is singular (e.g. a zero scale factor)
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if f64::abs(det) < 1e-12 {
+            return None;
+        }
+        let m = &self.m;
+        let inv_det = 1. / det;
+        // Inverse of the linear part is the adjugate divided by the determinant
+        let inv = [
+            [
+                (m[1][1] * m[2][2] - m[1][2] * m[2][1]) * inv_det,
+                (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det,
+                (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det,
+            ],
+            [
+                (m[1][2] * m[2][0] - m[1][0] * m[2][2]) * inv_det,
+                (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det,
+                (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det,
+            ],
+            [
+                (m[1][0] * m[2][1] - m[1][1] * m[2][0]) * inv_det,
+                (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det,
+                (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det,
+            ],
+        ];
+        let linear = Self::new(inv, Vec3::default());
+        Some(Self::new(inv, -linear.transform_vector(&self.t)))
+    }
+
+    /// Box enclosing the eight transformed corners of `bbox`
+    pub fn transform_box(&self, bbox: &AABB) -> AABB {
+        let (x, y, z) = (
+            bbox.axis_interval(0),
+            bbox.axis_interval(1),
+            bbox.axis_interval(2),
+        );
+        if x.size() < 0. || y.size() < 0. || z.size() < 0. {
+            return AABB::empty();
+        }
+        let mut min = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = Point3::new(-f64::INFINITY, -f64::INFINITY, -f64::INFINITY);
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { x.min } else { x.max },
+                if i & 2 == 0 { y.min } else { y.max },
+                if i & 4 == 0 { z.min } else { z.max },
+            );
+            let p = self.transform_point(&corner);
+            for c in 0..3 {
+                min[c] = f64::min(min[c], p[c]);
+                max[c] = f64::max(max[c], p[c]);
+            }
+        }
+        AABB::with_points(&min, &max)
+    }
+}
+
+impl Mul for Affine {
+    type Output = Self;
+
+    // Composition: (a * b)(p) == a(b(p))
+    fn mul(self, other: Self) -> Self {
+        let mut m = [[0.; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Self::new(m, self.transform_point(&other.t))
+    }
+}
+
+/// Places a Hittable in the world through an affine transform. Rays are moved into the object's
+/// space, intersected there, and the hit point and normal are carried back into world space.
+#[derive(Debug)]
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    forward: Affine,
+    inverse: Affine,
+    bbox: AABB,
+}
+
+impl Transform {
+    /// Panics if `forward` is not invertible
+    pub fn new(object: Arc<dyn Hittable>, forward: Affine) -> Self {
+        let inverse = forward
+            .inverse()
+            .expect("Transform requires an invertible matrix");
+        let bbox = forward.transform_box(&object.bounding_box());
+        Self {
+            object,
+            forward,
+            inverse,
+            bbox,
+        }
+    }
+
+    pub fn translate(object: Arc<dyn Hittable>, offset: Vec3) -> Self {
+        Self::new(object, Affine::translation(offset))
+    }
+
+    pub fn rotate(object: Arc<dyn Hittable>, axis: Vec3, degrees: f64) -> Self {
+        Self::new(object, Affine::rotation(axis, degrees))
+    }
+
+    pub fn rotate_y(object: Arc<dyn Hittable>, degrees: f64) -> Self {
+        Self::rotate(object, Vec3::new(0., 1., 0.), degrees)
+    }
+
+    pub fn scale(object: Arc<dyn Hittable>, factors: Vec3) -> Self {
+        Self::new(object, Affine::scale(factors))
+    }
+}
+
+/// Intersects `object` as seen through `forward`, given its precomputed `inverse`
+pub fn hit_transformed(
+    object: &dyn Hittable,
+    forward: &Affine,
+    inverse: &Affine,
+    r: &Ray,
+    ray_t: Interval,
+    rec: &mut HitRecord,
+) -> bool {
+    // The direction is not normalized after the change of space, so the ray parameter t
+    // stays the same in both spaces
+    let object_r = Ray::new_tm(
+        inverse.transform_point(&r.origin()),
+        inverse.transform_vector(&r.direction()),
+        r.time(),
+    );
+    if !object.hit(&object_r, ray_t, rec) {
+        return false;
+    }
+
+    to_world(forward, inverse, rec);
+    true
+}
+
+/// Like hit_transformed, but returns every intersection along the ray
+pub fn hit_all_transformed(
+    object: &dyn Hittable,
+    forward: &Affine,
+    inverse: &Affine,
+    r: &Ray,
+    ray_t: Interval,
+) -> Vec<HitRecord> {
+    let object_r = Ray::new_tm(
+        inverse.transform_point(&r.origin()),
+        inverse.transform_vector(&r.direction()),
+        r.time(),
+    );
+    let mut hits = object.hit_all(&object_r, ray_t);
+    for rec in hits.iter_mut() {
+        to_world(forward, inverse, rec);
+    }
+    hits
+}
+
+fn to_world(forward: &Affine, inverse: &Affine, rec: &mut HitRecord) {
+    // Normals transform with the inverse transpose of the linear part. The object already
+    // oriented the normal against the ray, and that orientation is preserved.
+    rec.p = forward.transform_point(&rec.p);
+    rec.normal = unit_vector(&inverse.transform_vector_transposed(&rec.normal));
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        hit_transformed(
+            self.object.as_ref(),
+            &self.forward,
+            &self.inverse,
+            r,
+            ray_t,
+            rec,
+        )
+    }
+
+    fn hit_all(&self, r: &Ray, ray_t: Interval) -> Vec<HitRecord> {
+        hit_all_transformed(self.object.as_ref(), &self.forward, &self.inverse, r, ray_t)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian, sphere::Sphere};
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(
+            Point3::new(0., 0., 0.),
+            1.,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let a = Affine::scale(Vec3::new(1., 2., 3.))
+            .then(&Affine::rotation(Vec3::new(1., 1., 0.), 30.))
+            .then(&Affine::translation(Vec3::new(4., 5., 6.)));
+        let p = Point3::new(-1., 0.5, 2.);
+        let inverse = a.inverse().unwrap();
+        assert_close(inverse.transform_point(&a.transform_point(&p)), p);
+        assert!(Affine::scale(Vec3::new(1., 0., 1.)).inverse().is_none());
+    }
+
+    #[test]
+    fn translated_sphere_hit() {
+        let moved = Transform::translate(unit_sphere(), Vec3::new(0., 0., -5.));
+        let r = Ray::new(Point3::new(0., 0., 0.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(moved.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!((rec.t - 4.).abs() < 1e-9);
+        assert_close(rec.p, Point3::new(0., 0., -4.));
+        assert_close(rec.normal, Vec3::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn scaled_normal_and_bounding_box() {
+        // Squash the sphere into an ellipsoid, the normal at (1, 1, 0)/sqrt(2) on the sphere
+        // maps to a normal tilted towards the squashed axis
+        let squashed = Transform::scale(unit_sphere(), Vec3::new(2., 1., 1.));
+        let bbox = squashed.bounding_box();
+        assert!((bbox.axis_interval(0).max - 2.).abs() < 1e-9);
+        assert!((bbox.axis_interval(1).max - 1.).abs() < 1e-9);
+
+        let r = Ray::new(Point3::new(f64::sqrt(2.), 5., 0.), Vec3::new(0., -1., 0.));
+        let mut rec = HitRecord::default();
+        assert!(squashed.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert_close(rec.normal, unit_vector(&Vec3::new(0.5, 1., 0.)));
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    aabb::AABB,
+    hittable::{HitRecord, Hittable},
+    interval::Interval,
+    material::Material,
+    ray::{Point3, Ray},
+    vec3::{cross, dot, unit_vector, Vec3},
+};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Triangle {
+    v0: Point3,
+    e1: Vec3,                     // v1 - v0
+    e2: Vec3,                     // v2 - v0
+    normal: Vec3,                 // geometric (face) normal
+    normals: Option<[Vec3; 3]>,   // per-vertex normals for smooth shading
+    uvs: Option<[(f64, f64); 3]>, // per-vertex texture coordinates
+    material: Option<Arc<dyn Material>>,
+    bbox: AABB,
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, material: Arc<dyn Material>) -> Self {
+        Self::with_attributes([v0, v1, v2], None, None, material)
+    }
+
+    pub fn with_attributes(
+        vertices: [Point3; 3],
+        normals: Option<[Vec3; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let [v0, v1, v2] = vertices;
+        let e1 = v1 - v0;
+        let e2 = v2 - v0;
+        let bbox = AABB::with_boxes(&AABB::with_points(&v0, &v1), &AABB::with_points(&v0, &v2));
+        Self {
+            v0,
+            e1,
+            e2,
+            normal: unit_vector(&cross(e1, e2)),
+            normals,
+            uvs,
+            material: Some(material),
+            bbox,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+        // Moller-Trumbore intersection, solving for the barycentric coordinates (b1, b2) and the
+        // ray parameter t at the same time
+        let pvec = cross(r.direction(), self.e2);
+        let det = dot(self.e1, pvec);
+
+        // No hit if the ray is parallel to the triangle plane
+        if f64::abs(det) < 1e-12 {
+            return false;
+        }
+        let inv_det = 1. / det;
+
+        let tvec = r.origin() - self.v0;
+        let b1 = dot(tvec, pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return false;
+        }
+
+        let qvec = cross(tvec, self.e1);
+        let b2 = dot(r.direction(), qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return false;
+        }
+
+        let t = dot(self.e2, qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+        let b0 = 1. - b1 - b2;
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.material = self.material.clone();
+        (rec.u, rec.v) = match self.uvs {
+            Some([uv0, uv1, uv2]) => (
+                b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+            ),
+            None => (b1, b2),
+        };
+
+        // The face is decided by the geometric normal, the interpolated vertex normal is only
+        // used for shading and is flipped to agree with it
+        rec.set_face_normal(r, &self.normal);
+        if let Some([n0, n1, n2]) = self.normals {
+            let shading = unit_vector(&(n0 * b0 + n1 * b1 + n2 * b2));
+            if !shading.near_zero() && shading.x().is_finite() {
+                rec.normal = if dot(shading, rec.normal) < 0. {
+                    -shading
+                } else {
+                    shading
+                };
+            }
+        }
+        true
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, material::Lambertian};
+
+    #[test]
+    fn smooth_normal_is_interpolated() {
+        let n0 = unit_vector(&Vec3::new(-1., 0., 1.));
+        let n1 = unit_vector(&Vec3::new(1., 0., 1.));
+        let tri = Triangle::with_attributes(
+            [
+                Point3::new(-1., 0., 0.),
+                Point3::new(1., 0., 0.),
+                Point3::new(0., 1., 0.),
+            ],
+            Some([n0, n1, Vec3::new(0., 0., 1.)]),
+            None,
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+        );
+        // Halfway along the bottom edge the two tilted normals average out to +z
+        let r = Ray::new(Point3::new(0., 0.001, 1.), Vec3::new(0., 0., -1.));
+        let mut rec = HitRecord::default();
+        assert!(tri.hit(&r, Interval::new(0.001, f64::INFINITY), &mut rec));
+        assert!(rec.front_face);
+        assert!((rec.normal - Vec3::new(0., 0., 1.)).length() < 1e-2);
+    }
+}
@@ -1,5 +1,5 @@
-use rand::{distributions::Uniform, prelude::Distribution, Rng};
-use std::f64;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use std::{cell::RefCell, f64};
 
 use js_sys::Promise;
 use wasm_bindgen::prelude::*;
@@ -8,20 +8,146 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     return degrees * f64::consts::PI / 180.;
 }
 
+// Every random number comes from a per-thread generator. The renderer traces each pixel sample
+// with a generator seeded for that sample (see with_seed), so a render only depends on its seed
+// and not on how rayon schedules the work.
+// Outside of renders (e.g. while building a scene) the stream starts from DEFAULT_SEED on every
+// thread unless seed_random is called.
+pub const DEFAULT_SEED: u64 = 0;
+
+thread_local! {
+    static RNG: RefCell<Pcg32> = RefCell::new(Pcg32::seed_from_u64(DEFAULT_SEED));
+}
+
+/// Restarts the random stream of the current thread
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = Pcg32::seed_from_u64(seed));
+}
+
+/// Runs `f` with the random stream of the current thread restarted from `seed`, then gives the
+/// thread its previous stream back. Renders run samples on the caller's thread too, whose stream
+/// must not change under it.
+pub fn with_seed<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let saved = RNG.with(|rng| rng.replace(Pcg32::seed_from_u64(seed)));
+    let result = f();
+    RNG.with(|rng| *rng.borrow_mut() = saved);
+    result
+}
+
+/// Runs `f` with the random generator of the current thread
+pub fn with_rng<T>(f: impl FnOnce(&mut Pcg32) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Seed of the random stream used for one sample of one pixel
+pub fn sample_seed(seed: u64, pixel: u64, sample: u64) -> u64 {
+    mix64(mix64(seed ^ mix64(pixel)) ^ sample)
+}
+
+// SplitMix64 finalizer, turns neighbouring inputs into unrelated outputs
+fn mix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
 pub fn random_double() -> f64 {
-    let between = Uniform::from(0.0..1.0);
-    let mut rng = rand::thread_rng();
-    between.sample(&mut rng)
+    with_rng(|rng| rng.gen::<f64>())
 }
 
 pub fn random_double_range(min: f64, max: f64) -> f64 {
-    rand::thread_rng().gen_range(min..max)
+    min + (max - min) * random_double()
 }
 
 pub fn random_int(min: i32, max: i32) -> i32 {
     random_double_range(min as f64, max as f64 + 1.) as i32
 }
 
+/// PCG32 (XSH RR variant) by Melissa O'Neill, a small and fast generator whose output is fully
+/// determined by its seed, on every platform
+#[derive(Debug, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64, // selects the stream, always odd
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    pub fn new(state: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(state);
+        rng.step();
+        rng
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let low = self.next_u32() as u64;
+        let high = self.next_u32() as u64;
+        (high << 32) | low
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Pcg32 {
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let state = u64::from_le_bytes(seed[..8].try_into().unwrap());
+        let stream = u64::from_le_bytes(seed[8..].try_into().unwrap());
+        Self::new(state, stream)
+    }
+}
+
+/// Milliseconds since an arbitrary point in time. std::time::Instant panics in the browser, so
+/// time budgets are measured with this instead.
+pub fn now_ms() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0., |elapsed| elapsed.as_secs_f64() * 1000.)
+    }
+}
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console)]
@@ -35,3 +161,46 @@ pub fn initialize(num_threads: usize, set_panic_hook: bool) -> Promise {
     }
     wasm_bindgen_rayon::init_thread_pool(num_threads)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_streams_repeat() {
+        seed_random(42);
+        let first: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        seed_random(42);
+        let second: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        assert_eq!(first, second);
+        assert!(first.iter().all(|x| (0. ..1.).contains(x)));
+
+        seed_random(43);
+        assert_ne!(first[0], random_double());
+    }
+
+    #[test]
+    fn with_seed_restores_the_stream() {
+        seed_random(42);
+        let expected: Vec<f64> = (0..4).map(|_| random_double()).collect();
+        seed_random(42);
+        let first = random_double();
+        let inner = with_seed(7, random_double);
+        let rest: Vec<f64> = (0..3).map(|_| random_double()).collect();
+        assert_eq!(first, expected[0]);
+        assert_eq!(rest, expected[1..]);
+        assert_eq!(inner, with_seed(7, random_double));
+    }
+
+    #[test]
+    fn pcg32_reference_output() {
+        // First outputs of the reference pcg32 demo (seed 42, stream 54)
+        let mut rng = Pcg32::new(42, 54);
+        let expected = [
+            0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e,
+        ];
+        for e in expected {
+            assert_eq!(rng.next_u32(), e);
+        }
+    }
+}
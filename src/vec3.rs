@@ -2,7 +2,10 @@ use serde::Serialize;
 
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
 
-use crate::utils::{random_double, random_double_range};
+use crate::{
+    sampler::square_to_cosine_hemisphere,
+    utils::{random_double, random_double_range},
+};
 
 #[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
 pub struct Vec3 {
@@ -60,6 +63,11 @@ impl Vec3 {
             return -on_unit_sphere;
         }
     }
+    // Cosine weighted direction around the +z axis, used to importance sample diffuse surfaces
+    pub fn random_cosine_direction() -> Self {
+        let (x, y, z) = square_to_cosine_hemisphere(random_double(), random_double());
+        Self::new(x, y, z)
+    }
     pub fn reflect(v: &Self, n: &Self) -> Self {
         return *v - *n * (2. * dot(*v, *n));
     }